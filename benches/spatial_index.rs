@@ -0,0 +1,112 @@
+//! Compares the `index_map` system when only a few monsters moved since the last frame
+//! with the full rebuild it does after a `MapReplacedEvent`, on a populated world.
+//!
+//! Run with `cargo bench --bench spatial_index`.
+
+use bevy::{
+    ecs::{event::Events, schedule::Stage},
+    prelude::*,
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use roguelike_jam_2::{
+    components::{Blocker, Opaque, Position},
+    events::MapReplacedEvent,
+    map::{index_map, Map},
+    map_tile::TileType,
+};
+
+const MONSTERS: u32 = 200;
+const MOVED_PER_FRAME: usize = 20;
+
+/// A `size`×`size` level with walls around and dotted about, and a crowd of monsters,
+/// already indexed once. Returns the monsters along with the world.
+fn level(size: usize) -> (World, SystemStage, Vec<Entity>) {
+    let mut world = World::new();
+    world.insert_resource(Map::new(size, size));
+    world.insert_resource(Events::<MapReplacedEvent>::default());
+
+    for y in 0..size {
+        for x in 0..size {
+            let wall = x == 0 || y == 0 || x == size - 1 || y == size - 1 || (x * 7 + y) % 11 == 0;
+            let mut tile = world.spawn();
+            tile.insert(Position::new(x, y));
+            if wall {
+                tile.insert(TileType::Wall).insert(Blocker).insert(Opaque);
+            } else {
+                tile.insert(TileType::Floor);
+            }
+        }
+    }
+    let monsters = (0..MONSTERS as usize)
+        .map(|i| {
+            world
+                .spawn()
+                .insert(Position::new(1 + i % (size - 2), 1 + (i * 13) % (size - 2)))
+                .insert(Blocker)
+                .id()
+        })
+        .collect();
+
+    let mut stage = SystemStage::single(index_map);
+    world
+        .get_resource_mut::<Events<MapReplacedEvent>>()
+        .unwrap()
+        .send(MapReplacedEvent);
+    frame(&mut world, &mut stage);
+    (world, stage, monsters)
+}
+
+/// Runs the system the way the game does once a frame.
+fn frame(world: &mut World, stage: &mut SystemStage) {
+    stage.run(world);
+    world.clear_trackers();
+    world
+        .get_resource_mut::<Events<MapReplacedEvent>>()
+        .unwrap()
+        .update();
+}
+
+fn bench_index(c: &mut Criterion) {
+    let mut group = c.benchmark_group("index_map per frame");
+    for size in [80, 200, 500] {
+        let (mut world, mut stage, monsters) = level(size);
+        let mut step = 1;
+        group.bench_function(BenchmarkId::new("incremental", size), |b| {
+            b.iter(|| {
+                // A few monsters step back and forth, the rest of the level stays put
+                step = -step;
+                for &monster in monsters.iter().take(MOVED_PER_FRAME) {
+                    world.get_mut::<Position>(monster).unwrap().x += step;
+                }
+                frame(&mut world, &mut stage);
+                black_box(
+                    world
+                        .get_resource::<Map>()
+                        .unwrap()
+                        .is_blocked(&Position::new(1, 1)),
+                )
+            })
+        });
+
+        let (mut world, mut stage, _) = level(size);
+        group.bench_function(BenchmarkId::new("rebuild", size), |b| {
+            b.iter(|| {
+                world
+                    .get_resource_mut::<Events<MapReplacedEvent>>()
+                    .unwrap()
+                    .send(MapReplacedEvent);
+                frame(&mut world, &mut stage);
+                black_box(
+                    world
+                        .get_resource::<Map>()
+                        .unwrap()
+                        .is_blocked(&Position::new(1, 1)),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_index);
+criterion_main!(benches);
@@ -0,0 +1,293 @@
+//! Runs the map generators without opening a window and prints the levels they build.
+//!
+//! ```text
+//! cargo run --bin mapgen -- --builder caves --seed 42 --count 5 --format json
+//! ```
+
+use std::process;
+
+use bevy_ascii_terminal::Tile;
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+use roguelike_jam_2::{
+    map_builders::{MapBuilderKind, MapInfo, MapTransform, Prefab, Spawn},
+    settings::{parse_map_size, MapSettings},
+    spawn_table::{SpawnEntry, SpawnTable},
+    MAP_SIZE,
+};
+use serde::Serialize;
+
+const USAGE: &str = "Usage: mapgen [OPTIONS]
+
+Options:
+    --builder <name>     rooms, bsp, caves, drunkard or dla [default: rooms]
+    --width <n>          map width, at least 14 [default: game map width]
+    --height <n>         map height, at least 14 [default: game map height]
+    --seed <n>           seed of the first map [default: random]
+    --count <n>          how many maps to build, with consecutive seeds [default: 1]
+    --depth <n>          dungeon depth the maps are built for [default: 1]
+    --prefabs <dir>      directory with prefab files to stamp into the maps
+    --transform <name>   mirror-x, mirror-y, rotate-90, rotate-180 or rotate-270
+    --format <format>    ascii or json [default: ascii]
+    -h, --help           print this message";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    Json,
+}
+
+struct Options {
+    builder: String,
+    width: usize,
+    height: usize,
+    seed: u64,
+    count: u64,
+    depth: u32,
+    prefabs: Option<String>,
+    transform: Option<MapTransform>,
+    format: Format,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            builder: "rooms".to_string(),
+            width: MAP_SIZE[0] as usize,
+            height: MAP_SIZE[1] as usize,
+            seed: RandomNumberGenerator::new().next_u64(),
+            count: 1,
+            depth: 1,
+            prefabs: None,
+            transform: None,
+            format: Format::Ascii,
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} expects a number, got {:?}", option, value))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--builder" => options.builder = value()?,
+            "--width" => options.width = parse_map_size(&arg, &value()?)?,
+            "--height" => options.height = parse_map_size(&arg, &value()?)?,
+            "--seed" => options.seed = parse_number(&arg, value()?)?,
+            "--count" => options.count = parse_number(&arg, value()?)?,
+            "--depth" => options.depth = parse_number(&arg, value()?)?,
+            "--prefabs" => options.prefabs = Some(value()?),
+            "--transform" => options.transform = Some(value()?.parse()?),
+            "--format" => {
+                options.format = match value()?.as_str() {
+                    "ascii" => Format::Ascii,
+                    "json" => Format::Json,
+                    other => return Err(format!("Unknown format: {}", other)),
+                }
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+#[derive(Serialize)]
+struct JsonPoint {
+    x: i32,
+    y: i32,
+}
+
+impl From<Point> for JsonPoint {
+    fn from(point: Point) -> Self {
+        Self {
+            x: point.x,
+            y: point.y,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRoom {
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+}
+
+#[derive(Serialize)]
+struct JsonSpawn {
+    x: i32,
+    y: i32,
+    kind: &'static str,
+    name: String,
+}
+
+#[derive(Serialize)]
+struct JsonMap {
+    seed: u64,
+    builder: String,
+    width: usize,
+    height: usize,
+    /// Rows from top to bottom, one glyph per tile
+    tiles: Vec<String>,
+    rooms: Vec<JsonRoom>,
+    player_start: Option<JsonPoint>,
+    spawns: Vec<JsonSpawn>,
+}
+
+fn spawn_glyph(spawn: Spawn) -> char {
+    match spawn {
+        Spawn::Monster => 'M',
+        Spawn::Item => '!',
+    }
+}
+
+fn kind_name(spawn: Spawn) -> &'static str {
+    match spawn {
+        Spawn::Monster => "monster",
+        Spawn::Item => "item",
+    }
+}
+
+/// Glyph rows from top to bottom, since y grows upwards in the game.
+fn glyph_rows(map: &MapInfo, spawns: &[(Point, SpawnEntry)], marked: bool) -> Vec<String> {
+    let mut glyphs = map
+        .tiles
+        .iter()
+        .map(|tile| Tile::from(tile).glyph)
+        .collect::<Vec<_>>();
+
+    if marked {
+        for (point, entry) in spawns {
+            if let Some(idx) = map.xy_idx(point.x, point.y) {
+                glyphs[idx] = spawn_glyph(entry.kind);
+            }
+        }
+        if let Some(idx) = map
+            .player_start
+            .and_then(|start| map.xy_idx(start.x, start.y))
+        {
+            glyphs[idx] = '@';
+        }
+    }
+
+    glyphs
+        .chunks(map.width)
+        .rev()
+        .map(|row| row.iter().collect())
+        .collect()
+}
+
+fn print_ascii(seed: u64, map: &MapInfo, spawns: &[(Point, SpawnEntry)]) {
+    println!("Seed: {}", seed);
+    for row in glyph_rows(map, spawns, true) {
+        println!("{}", row);
+    }
+    match map.player_start {
+        Some(start) => println!("Player start: ({}, {})", start.x, start.y),
+        None => println!("Player start: none"),
+    }
+    for (i, room) in map.rooms.iter().enumerate() {
+        println!(
+            "Room {}: ({}, {}) - ({}, {})",
+            i, room.x1, room.y1, room.x2, room.y2
+        );
+    }
+    for (point, entry) in spawns {
+        println!(
+            "Spawn {} {}: ({}, {})",
+            kind_name(entry.kind),
+            entry.name,
+            point.x,
+            point.y
+        );
+    }
+    println!();
+}
+
+fn to_json(seed: u64, builder: &str, map: &MapInfo, spawns: &[(Point, SpawnEntry)]) -> JsonMap {
+    JsonMap {
+        seed,
+        builder: builder.to_string(),
+        width: map.width,
+        height: map.height,
+        tiles: glyph_rows(map, spawns, false),
+        rooms: map
+            .rooms
+            .iter()
+            .map(|room| JsonRoom {
+                x1: room.x1,
+                y1: room.y1,
+                x2: room.x2,
+                y2: room.y2,
+            })
+            .collect(),
+        player_start: map.player_start.map(JsonPoint::from),
+        spawns: spawns
+            .iter()
+            .map(|(point, entry)| JsonSpawn {
+                x: point.x,
+                y: point.y,
+                kind: kind_name(entry.kind),
+                name: entry.name.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let builder = options.builder.parse::<MapBuilderKind>()?;
+    let prefabs = match &options.prefabs {
+        Some(dir) => Prefab::load_dir(dir)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| err.to_string())?,
+        None => Vec::new(),
+    };
+    let settings = MapSettings {
+        width: options.width,
+        height: options.height,
+        builder,
+        prefabs_dir: options.prefabs.clone().unwrap_or_default(),
+        prefabs,
+        transform: options.transform,
+        spawn_table: SpawnTable::dungeon(),
+    };
+
+    let mut maps = Vec::new();
+    for seed in (0..options.count).map(|i| options.seed.wrapping_add(i)) {
+        // Same order of rolls as the game, so a seed shows the level the game would build
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let map = settings.create_builder(options.depth).build(&mut rng);
+        let spawns = map.roll_spawns(&mut rng, &settings.spawn_table, options.depth);
+
+        match options.format {
+            Format::Ascii => print_ascii(seed, &map, &spawns),
+            Format::Json => maps.push(to_json(seed, &options.builder, &map, &spawns)),
+        }
+    }
+
+    if options.format == Format::Json {
+        let json = serde_json::to_string_pretty(&maps).map_err(|err| err.to_string())?;
+        println!("{}", json);
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = parse_args().and_then(run) {
+        eprintln!("mapgen: {}", err);
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+}
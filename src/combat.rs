@@ -1,10 +1,19 @@
+use std::str::FromStr;
+
 use bevy::prelude::*;
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
 use bracket_lib::prelude::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
 
-use crate::{events::AttackEvent, log::GameLog};
+use crate::{
+    components::Player,
+    events::AttackEvent,
+    log::GameLog,
+    resources::{GameState, RunStats},
+    rng::GameRng,
+};
 
-#[derive(Clone, Copy, Inspectable)]
+#[derive(Debug, Clone, Copy, Inspectable, Serialize, Deserialize)]
 pub struct Dice {
     count: i32,
     sides: i32,
@@ -18,6 +27,30 @@ impl Dice {
     }
 }
 
+/// Parses dice notation like `1d6`, `2d4+1` or `1d8-2`.
+impl FromStr for Dice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("{:?} is not a dice roll like \"1d6\" or \"2d4+1\"", s);
+        let (count, rest) = s.trim().split_once('d').ok_or_else(invalid)?;
+        let (sides, modifier) = match rest.find(|c| c == '+' || c == '-') {
+            Some(at) => rest.split_at(at),
+            None => (rest, "+0"),
+        };
+        let count = count.parse::<i32>().map_err(|_| invalid())?;
+        let sides = sides.parse::<i32>().map_err(|_| invalid())?;
+        let modifier = modifier
+            .trim_start_matches('+')
+            .parse::<i32>()
+            .map_err(|_| invalid())?;
+        if count < 1 || sides < 1 {
+            return Err(invalid());
+        }
+        Ok(Self::from((count, sides, modifier)))
+    }
+}
+
 impl<T> From<(T, T, T)> for Dice
 where
     T: Into<i32>,
@@ -43,7 +76,7 @@ where
     }
 }
 
-#[derive(Component, Clone, Copy, Inspectable)]
+#[derive(Component, Clone, Copy, Inspectable, Serialize, Deserialize)]
 pub struct Attack {
     dice: Dice,
 }
@@ -54,7 +87,7 @@ impl Attack {
     }
 }
 
-#[derive(Component, Clone, Copy, Inspectable)]
+#[derive(Component, Clone, Copy, Inspectable, Serialize, Deserialize)]
 pub struct Health {
     pub current: u32,
     pub max: u32,
@@ -87,18 +120,21 @@ pub struct CombatPlugin;
 
 impl Plugin for CombatPlugin {
     fn build(&self, app: &mut App) {
-        app.register_inspectable::<Health>()
+        app.init_resource::<RunStats>()
+            .register_inspectable::<Health>()
             .register_inspectable::<Attack>();
     }
 }
 
 pub fn combat(
     mut game_log: ResMut<GameLog>,
+    mut rng: ResMut<GameRng>,
+    mut stats: ResMut<RunStats>,
     mut attack_events: EventReader<AttackEvent>,
     mut attackers: Query<(&Name, &Attack)>,
     mut victims: Query<(&mut Health, Option<&Name>)>,
+    players: Query<(), With<Player>>,
 ) {
-    let mut rng = RandomNumberGenerator::new();
     for event in attack_events.iter() {
         let (attacker_name, attack) = match attackers.get_mut(event.attacker) {
             Ok(attacker) => attacker,
@@ -111,27 +147,43 @@ pub fn combat(
 
         let damage = attack.dice.roll(&mut rng);
 
+        let was_alive = !victim_health.is_dead();
         victim_health.take_damage(damage);
         let victim_name = victim_name.cloned().unwrap_or_else(|| Name::new("Unknown"));
         game_log.push(format!(
             "{} attacks {} with {} damage",
             attacker_name, victim_name, damage
         ));
+
+        if was_alive && victim_health.is_dead() {
+            if players.contains(event.attacker) {
+                *stats.kills.entry(victim_name.to_string()).or_default() += 1;
+            }
+            if players.contains(event.target) {
+                stats.cause_of_death = Some(format!("Killed by {}", attacker_name));
+            }
+        }
     }
 }
 
+/// Despawns whatever died, except the player, who stays around for the death screen.
 pub fn track_dead(
     mut game_log: ResMut<GameLog>,
     mut commands: Commands,
-    actors: Query<(Entity, &Health, Option<&Name>), Changed<Health>>,
+    mut states: ResMut<State<GameState>>,
+    actors: Query<(Entity, &Health, Option<&Name>, Option<&Player>), Changed<Health>>,
 ) {
-    for (entity, health, name) in actors.iter() {
+    for (entity, health, name, player) in actors.iter() {
         if health.is_dead() {
             game_log.push(format!(
                 "{} died",
                 name.cloned().unwrap_or_else(|| Name::new("Unknown"))
             ));
-            commands.entity(entity).despawn_recursive();
+            if player.is_some() {
+                states.overwrite_set(GameState::GameOver).ok();
+            } else {
+                commands.entity(entity).despawn_recursive();
+            }
         }
     }
 }
@@ -2,6 +2,9 @@ use std::collections::HashSet;
 
 use bevy::prelude::*;
 use bracket_lib::prelude::Point;
+use serde::{Deserialize, Serialize};
+
+use crate::fov::FovAlgorithm;
 
 #[derive(Component)]
 pub struct MapViewTerminal;
@@ -12,7 +15,9 @@ pub struct LogTerminal;
 #[derive(Component)]
 pub struct StatusTerminal;
 
-#[derive(Component, Default, PartialEq, Eq, Clone, Copy, Ord, PartialOrd)]
+#[derive(
+    Component, Default, PartialEq, Eq, Clone, Copy, Ord, PartialOrd, Serialize, Deserialize,
+)]
 pub struct Layer(pub u32);
 
 #[derive(Component, Clone, Copy)]
@@ -30,7 +35,7 @@ pub struct Unrevealable;
 #[derive(Component, Clone, Copy)]
 pub struct Visible;
 
-#[derive(Component, Default, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Component, Default, Debug, PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
@@ -88,6 +93,8 @@ pub struct WantToMove {
 pub struct Fov {
     pub visible_tiles: HashSet<Position>,
     pub range: u32,
+    /// Overrides the algorithm from [`FovSettings`](crate::fov::FovSettings) for this entity.
+    pub algorithm: Option<FovAlgorithm>,
 }
 
 impl Fov {
@@ -95,8 +102,14 @@ impl Fov {
         Self {
             visible_tiles: HashSet::new(),
             range,
+            algorithm: None,
         }
     }
+
+    pub fn with_algorithm(mut self, algorithm: FovAlgorithm) -> Self {
+        self.algorithm = Some(algorithm);
+        self
+    }
 }
 
 #[derive(Default, Debug, Component)]
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+
+use crate::{
+    combat::Health,
+    components::{Energy, Position, Visible},
+    events::{OpenDoorEvent, WantCloseDoor},
+    handle_want_to_move,
+    items::Item,
+    log::GameLog,
+    map::Map,
+    map_tile::TileType,
+};
+
+pub struct DoorPlugin;
+
+impl Plugin for DoorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<OpenDoorEvent>()
+            .add_system(open_doors.after(handle_want_to_move))
+            .add_system(handle_want_close_door);
+    }
+}
+
+fn open_doors(
+    mut commands: Commands,
+    mut game_log: ResMut<GameLog>,
+    mut open_door_events: EventReader<OpenDoorEvent>,
+    mut actors: Query<(&Name, &mut Energy)>,
+    visible: Query<&Visible>,
+) {
+    for event in open_door_events.iter() {
+        TileType::Door { open: true }.insert_into(&mut commands.entity(event.door));
+
+        if let Ok((name, mut energy)) = actors.get_mut(event.opener) {
+            energy.0 = 0;
+            if visible.contains(event.door) {
+                game_log.push(format!("{} opens the door", name));
+            }
+        }
+    }
+}
+
+fn handle_want_close_door(
+    mut commands: Commands,
+    mut game_log: ResMut<GameLog>,
+    map: Res<Map>,
+    mut actors: Query<(Entity, &Name, &Position, &mut Energy), With<WantCloseDoor>>,
+    tiles: Query<&TileType>,
+    occupants: Query<Entity, Or<(With<Health>, With<Item>)>>,
+) {
+    for (entity, name, position, mut energy) in actors.iter_mut() {
+        let neighbours = (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|&delta| delta != (0, 0))
+            .map(|(dx, dy)| Position::new(position.x + dx, position.y + dy));
+
+        // Something standing in the doorway keeps the door from closing
+        let door = neighbours
+            .map(|position| map.at_position(&position))
+            .filter(|entities| !entities.iter().any(|&e| occupants.contains(e)))
+            .find_map(|entities| {
+                entities
+                    .into_iter()
+                    .find(|&e| tiles.get(e).ok() == Some(&TileType::Door { open: true }))
+            });
+
+        match door {
+            Some(door) => {
+                TileType::Door { open: false }.insert_into(&mut commands.entity(door));
+                energy.0 = 0;
+                game_log.push(format!("{} closes the door", name));
+            }
+            None => game_log.push("There is no open door nearby"),
+        }
+        commands.entity(entity).remove::<WantCloseDoor>();
+    }
+}
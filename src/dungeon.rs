@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_ascii_terminal::Tile;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{Attack, CombatStatsBundle, Health},
+    components::{Energy, Fov, Layer, MovingEntityBundle, Position, Revealed, Unrevealable},
+    items::{Fuel, Item, Potion},
+    light::LightSource,
+    map::{spawn_map_root, Map},
+    map_tile::{Stairs, TileType},
+    memory::{Ghost, Memory},
+    monster::MonsterBundle,
+    save::{name_format, tile_format},
+    LAYER_ITEM,
+};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredTile {
+    pub position: Position,
+    pub tile_type: TileType,
+    pub revealed: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredMonster {
+    pub position: Position,
+    #[serde(with = "name_format")]
+    pub name: Name,
+    #[serde(with = "tile_format")]
+    pub tile: Tile,
+    pub health: Health,
+    pub attack: Attack,
+    pub speed: i32,
+    pub energy: i32,
+    pub fov_range: u32,
+    pub light: Option<LightSource>,
+    /// How the player remembers the monster, if they saw it
+    pub ghost: Option<Ghost>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredItem {
+    pub position: Position,
+    #[serde(with = "name_format")]
+    pub name: Name,
+    #[serde(with = "tile_format")]
+    pub tile: Tile,
+    pub potion: Option<u32>,
+    pub fuel: Option<u32>,
+    pub ghost: Option<Ghost>,
+}
+
+/// A level the player has left, kept out of the ECS world until they come back.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StoredLevel {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<StoredTile>,
+    pub monsters: Vec<StoredMonster>,
+    pub items: Vec<StoredItem>,
+    /// Remembered things that were already gone when the level was left
+    pub ghosts: Vec<Ghost>,
+}
+
+impl StoredLevel {
+    pub fn stairs(&self, stairs: Stairs) -> Option<Position> {
+        self.tiles
+            .iter()
+            .find(|tile| tile.tile_type.stairs() == Some(stairs))
+            .map(|tile| tile.position)
+    }
+
+    /// Spawns the level back into the world exactly as it was stored, along with what the
+    /// player remembers of it.
+    pub fn restore(self, commands: &mut Commands, memory: &mut Memory) {
+        commands.insert_resource(Map::new(self.width, self.height));
+
+        let tile_entities = self
+            .tiles
+            .into_iter()
+            .map(|tile| {
+                let entity = tile.tile_type.spawn(commands, tile.position);
+                if tile.revealed {
+                    commands.entity(entity).insert(Revealed);
+                }
+                entity
+            })
+            .collect::<Vec<_>>();
+        spawn_map_root(commands, &tile_entities);
+
+        for monster in self.monsters {
+            let mut moving = MovingEntityBundle::new(monster.speed).with_position(monster.position);
+            moving.energy = Energy(monster.energy);
+            let mut entity = commands.spawn_bundle(MonsterBundle {
+                name: monster.name,
+                fov: Fov::new(monster.fov_range),
+                tile: monster.tile,
+                combat_stats: CombatStatsBundle {
+                    health: monster.health,
+                    attack: monster.attack,
+                },
+                moving,
+                ..Default::default()
+            });
+            if let Some(light) = monster.light {
+                entity.insert(light);
+            }
+            if let Some(ghost) = monster.ghost {
+                memory.remember(Some(entity.id()), ghost);
+            }
+        }
+
+        for item in self.items {
+            let mut entity = commands.spawn();
+            entity
+                .insert(Item)
+                .insert(item.tile)
+                .insert(item.name)
+                .insert(item.position)
+                .insert(Layer(LAYER_ITEM))
+                .insert(Unrevealable);
+            if let Some(heal_amount) = item.potion {
+                entity.insert(Potion { heal_amount });
+            }
+            if let Some(amount) = item.fuel {
+                entity.insert(Fuel { amount });
+            }
+            if let Some(ghost) = item.ghost {
+                memory.remember(Some(entity.id()), ghost);
+            }
+        }
+
+        for ghost in self.ghosts {
+            memory.remember(None, ghost);
+        }
+    }
+}
+
+/// Every level that isn't active right now, by depth.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Dungeon {
+    levels: HashMap<u32, StoredLevel>,
+}
+
+impl Dungeon {
+    pub fn store(&mut self, depth: u32, level: StoredLevel) {
+        self.levels.insert(depth, level);
+    }
+
+    pub fn take(&mut self, depth: u32) -> Option<StoredLevel> {
+        self.levels.remove(&depth)
+    }
+}
@@ -1,6 +1,26 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::components::Position;
+use crate::{components::Position, map_tile::Stairs, resources::Difficulty};
+
+/// Something the player decided to do on their turn, from the keyboard, the inventory
+/// menus or a replay. Backpack items are referred to by their slot in the menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PlayerAction {
+    Move { dx: i32, dy: i32 },
+    Wait,
+    PickUp,
+    CloseDoor,
+    ToggleLantern,
+    Descend,
+    Ascend,
+    UseItem { slot: usize },
+    DropItem { slot: usize },
+}
+
+/// The one [`PlayerAction`] that was actually carried out this frame, which is what a
+/// recording has to repeat.
+pub struct ActionTakenEvent(pub PlayerAction);
 
 pub struct AttackEvent {
     pub attacker: Entity,
@@ -19,3 +39,35 @@ pub struct PickupEvent {
     pub collected_by: Entity,
     pub item: Entity,
 }
+
+#[derive(Component)]
+pub struct WantTakeStairs(pub Stairs);
+
+pub struct ChangeLevelEvent {
+    pub depth: u32,
+}
+
+/// A new [`crate::map::Map`] was inserted along with a whole level, so it has to be
+/// indexed from scratch.
+pub struct MapReplacedEvent;
+
+pub struct OpenDoorEvent {
+    pub opener: Entity,
+    pub door: Entity,
+}
+
+#[derive(Component)]
+pub struct WantCloseDoor;
+
+#[derive(Component)]
+pub struct WantToggleLantern;
+
+/// Throws away whatever world there is and starts a run from scratch.
+pub struct NewRunEvent {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+}
+
+pub struct SaveGameEvent;
+
+pub struct LoadGameEvent;
@@ -0,0 +1,511 @@
+use std::{collections::HashSet, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{components::Position, map::Map, settings::parse_option};
+
+/// Works out which tiles can be seen from `origin`, up to `range` tiles away.
+/// The origin is always visible, and so are the walls that block the view.
+pub trait FieldOfView {
+    fn compute(&self, map: &Map, origin: Position, range: u32) -> HashSet<Position>;
+}
+
+/// The field of view algorithms to choose from, globally with `--fov <name>` or
+/// per entity through [`Fov::algorithm`](crate::components::Fov::algorithm).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FovAlgorithm {
+    RecursiveShadowcasting,
+    SymmetricShadowcasting,
+    Permissive,
+}
+
+impl Default for FovAlgorithm {
+    fn default() -> Self {
+        FovAlgorithm::SymmetricShadowcasting
+    }
+}
+
+impl FovAlgorithm {
+    pub const ALL: [FovAlgorithm; 3] = [
+        FovAlgorithm::RecursiveShadowcasting,
+        FovAlgorithm::SymmetricShadowcasting,
+        FovAlgorithm::Permissive,
+    ];
+
+    /// The name it goes by on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FovAlgorithm::RecursiveShadowcasting => "recursive",
+            FovAlgorithm::SymmetricShadowcasting => "symmetric",
+            FovAlgorithm::Permissive => "permissive",
+        }
+    }
+
+    pub fn compute(&self, map: &Map, origin: Position, range: u32) -> HashSet<Position> {
+        match self {
+            FovAlgorithm::RecursiveShadowcasting => {
+                RecursiveShadowcasting.compute(map, origin, range)
+            }
+            FovAlgorithm::SymmetricShadowcasting => {
+                SymmetricShadowcasting.compute(map, origin, range)
+            }
+            FovAlgorithm::Permissive => PermissiveFov.compute(map, origin, range),
+        }
+    }
+}
+
+impl FromStr for FovAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "recursive" => Ok(FovAlgorithm::RecursiveShadowcasting),
+            "symmetric" => Ok(FovAlgorithm::SymmetricShadowcasting),
+            "permissive" => Ok(FovAlgorithm::Permissive),
+            _ => Err(format!(
+                "Unknown FOV algorithm {:?}, expected one of: recursive, symmetric, permissive",
+                s
+            )),
+        }
+    }
+}
+
+/// The algorithm used by every entity that doesn't pick its own.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FovSettings {
+    pub algorithm: FovAlgorithm,
+}
+
+impl FovSettings {
+    /// Reads the algorithm from `--fov <name>` or `ROGUELIKE_FOV`.
+    pub fn from_env() -> Self {
+        Self {
+            algorithm: parse_option("--fov", "ROGUELIKE_FOV").unwrap_or_default(),
+        }
+    }
+}
+
+fn in_range(dx: i32, dy: i32, range: u32) -> bool {
+    dx * dx + dy * dy <= (range * range) as i32
+}
+
+/// Classic recursive shadowcasting, one octant at a time. Fast, but not symmetric:
+/// a tile may see another one that can't see it back.
+pub struct RecursiveShadowcasting;
+
+impl RecursiveShadowcasting {
+    /// Octant transforms as `[xx, xy, yx, yy]`.
+    const OCTANTS: [[i32; 4]; 8] = [
+        [1, 0, 0, 1],
+        [0, 1, 1, 0],
+        [0, -1, 1, 0],
+        [-1, 0, 0, 1],
+        [-1, 0, 0, -1],
+        [0, -1, -1, 0],
+        [0, 1, -1, 0],
+        [1, 0, 0, -1],
+    ];
+
+    #[allow(clippy::too_many_arguments)]
+    fn cast_light(
+        map: &Map,
+        origin: Position,
+        range: i32,
+        row: i32,
+        mut start: f32,
+        end: f32,
+        [xx, xy, yx, yy]: [i32; 4],
+        visible: &mut HashSet<Position>,
+    ) {
+        if start < end {
+            return;
+        }
+
+        let mut new_start = 0.0;
+        for j in row..=range {
+            let dy = -j;
+            let mut blocked = false;
+            for dx in -j..=0 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+                if start < right_slope {
+                    continue;
+                } else if end > left_slope {
+                    break;
+                }
+
+                let position =
+                    Position::new(origin.x + dx * xx + dy * xy, origin.y + dx * yx + dy * yy);
+                if in_range(dx, dy, range as u32) && map.is_in_bounds(&position) {
+                    visible.insert(position);
+                }
+
+                let opaque = map.is_opaque_at(&position);
+                if blocked {
+                    if opaque {
+                        new_start = right_slope;
+                    } else {
+                        blocked = false;
+                        start = new_start;
+                    }
+                } else if opaque && j < range {
+                    blocked = true;
+                    Self::cast_light(
+                        map,
+                        origin,
+                        range,
+                        j + 1,
+                        start,
+                        left_slope,
+                        [xx, xy, yx, yy],
+                        visible,
+                    );
+                    new_start = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+}
+
+impl FieldOfView for RecursiveShadowcasting {
+    fn compute(&self, map: &Map, origin: Position, range: u32) -> HashSet<Position> {
+        let mut visible = HashSet::new();
+        visible.insert(origin);
+        for octant in Self::OCTANTS {
+            Self::cast_light(map, origin, range as i32, 1, 1.0, 0.0, octant, &mut visible);
+        }
+        visible
+    }
+}
+
+/// Albert Ford's symmetric shadowcasting: if a floor tile can see another one,
+/// that one sees it back. Slopes are kept as exact fractions so it stays that way.
+pub struct SymmetricShadowcasting;
+
+/// A slope `num / den`, with `den` always positive.
+#[derive(Clone, Copy)]
+struct Slope {
+    num: i32,
+    den: i32,
+}
+
+impl Slope {
+    fn new(num: i32, den: i32) -> Self {
+        Self { num, den }
+    }
+
+    /// The slope through the near edge of the tile at `col` on row `depth`.
+    fn of_tile(depth: i32, col: i32) -> Self {
+        Self::new(2 * col - 1, 2 * depth)
+    }
+
+    /// `depth * self`, rounded to the nearest integer with ties going up.
+    fn round_ties_up(&self, depth: i32) -> i32 {
+        (2 * depth * self.num + self.den).div_euclid(2 * self.den)
+    }
+
+    /// `depth * self`, rounded to the nearest integer with ties going down.
+    fn round_ties_down(&self, depth: i32) -> i32 {
+        -(-(2 * depth * self.num - self.den)).div_euclid(2 * self.den)
+    }
+}
+
+impl SymmetricShadowcasting {
+    /// Maps a (depth, column) pair of one of the four quadrants back to the map.
+    fn transform(origin: Position, quadrant: u8, depth: i32, col: i32) -> Position {
+        match quadrant {
+            0 => Position::new(origin.x + col, origin.y + depth),
+            1 => Position::new(origin.x + depth, origin.y + col),
+            2 => Position::new(origin.x + col, origin.y - depth),
+            _ => Position::new(origin.x - depth, origin.y + col),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn scan(
+        map: &Map,
+        origin: Position,
+        range: u32,
+        quadrant: u8,
+        depth: i32,
+        mut start: Slope,
+        end: Slope,
+        visible: &mut HashSet<Position>,
+    ) {
+        if depth > range as i32 {
+            return;
+        }
+
+        let is_wall = |col: i32| map.is_opaque_at(&Self::transform(origin, quadrant, depth, col));
+        let mut prev_wall = None;
+        for col in start.round_ties_up(depth)..=end.round_ties_down(depth) {
+            let wall = is_wall(col);
+            let symmetric =
+                col * start.den >= depth * start.num && col * end.den <= depth * end.num;
+            if wall || symmetric {
+                let position = Self::transform(origin, quadrant, depth, col);
+                if in_range(col, depth, range) && map.is_in_bounds(&position) {
+                    visible.insert(position);
+                }
+            }
+            if prev_wall == Some(true) && !wall {
+                start = Slope::of_tile(depth, col);
+            }
+            if prev_wall == Some(false) && wall {
+                let next_end = Slope::of_tile(depth, col);
+                Self::scan(
+                    map,
+                    origin,
+                    range,
+                    quadrant,
+                    depth + 1,
+                    start,
+                    next_end,
+                    visible,
+                );
+            }
+            prev_wall = Some(wall);
+        }
+        if prev_wall == Some(false) {
+            Self::scan(map, origin, range, quadrant, depth + 1, start, end, visible);
+        }
+    }
+}
+
+impl FieldOfView for SymmetricShadowcasting {
+    fn compute(&self, map: &Map, origin: Position, range: u32) -> HashSet<Position> {
+        let mut visible = HashSet::new();
+        visible.insert(origin);
+        for quadrant in 0..4 {
+            Self::scan(
+                map,
+                origin,
+                range,
+                quadrant,
+                1,
+                Slope::new(-1, 1),
+                Slope::new(1, 1),
+                &mut visible,
+            );
+        }
+        visible
+    }
+}
+
+/// Sees a tile if any straight line from the middle of the origin reaches its middle or
+/// one of its corners without crossing an opaque tile. The most generous of the three,
+/// it sees through diagonal gaps and around pillars more than shadowcasting does.
+pub struct PermissiveFov;
+
+impl PermissiveFov {
+    /// Samples per tile along each line.
+    const STEPS_PER_TILE: f32 = 8.0;
+    /// Keeps the corner targets just inside the tile, so lines don't end in a neighbour.
+    const INSET: f32 = 0.49;
+
+    fn line_is_clear(map: &Map, origin: Position, target: Position, end: (f32, f32)) -> bool {
+        let dx = end.0 - origin.x as f32;
+        let dy = end.1 - origin.y as f32;
+        let steps = (dx.abs().max(dy.abs()) * Self::STEPS_PER_TILE).ceil() as i32;
+        (1..steps).all(|i| {
+            let t = i as f32 / steps as f32;
+            let sample = Position::new(
+                (origin.x as f32 + dx * t + 0.5).floor() as i32,
+                (origin.y as f32 + dy * t + 0.5).floor() as i32,
+            );
+            sample == origin || sample == target || !map.is_opaque_at(&sample)
+        })
+    }
+}
+
+impl FieldOfView for PermissiveFov {
+    fn compute(&self, map: &Map, origin: Position, range: u32) -> HashSet<Position> {
+        let mut visible = HashSet::new();
+        visible.insert(origin);
+        let range = range as i32;
+        for dy in -range..=range {
+            for dx in -range..=range {
+                let target = Position::new(origin.x + dx, origin.y + dy);
+                if (dx, dy) == (0, 0)
+                    || !in_range(dx, dy, range as u32)
+                    || !map.is_in_bounds(&target)
+                {
+                    continue;
+                }
+                let (x, y) = (target.x as f32, target.y as f32);
+                let ends = [
+                    (x, y),
+                    (x - Self::INSET, y - Self::INSET),
+                    (x - Self::INSET, y + Self::INSET),
+                    (x + Self::INSET, y - Self::INSET),
+                    (x + Self::INSET, y + Self::INSET),
+                ];
+                if ends
+                    .iter()
+                    .any(|&end| Self::line_is_clear(map, origin, target, end))
+                {
+                    visible.insert(target);
+                }
+            }
+        }
+        visible
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::Entity;
+
+    use super::*;
+    use crate::{map::MapEntry, map_tile::TileType};
+
+    /// Builds a map from rows written top to bottom: `#` is a wall, `.` floor and `@`
+    /// the origin, which is returned along with the map.
+    fn fixture(rows: &[&str]) -> (Map, Position) {
+        let height = rows.len();
+        let width = rows[0].len();
+        let mut map = Map::new(width, height);
+        let mut origin = None;
+        for (row, line) in rows.iter().enumerate() {
+            let y = height - 1 - row;
+            for (x, c) in line.chars().enumerate() {
+                let position = Position::new(x, y);
+                let tile_type = if c == '#' {
+                    TileType::Wall
+                } else {
+                    TileType::Floor
+                };
+                if c == '@' {
+                    origin = Some(position);
+                }
+                map.index(
+                    Entity::from_raw((y * width + x) as u32),
+                    MapEntry {
+                        position,
+                        tile_type: Some(tile_type),
+                        blocks: tile_type.is_blocking(),
+                        opaque: tile_type.is_opaque(),
+                        closed_door: false,
+                    },
+                );
+            }
+        }
+        (map, origin.expect("fixture has no origin '@'"))
+    }
+
+    #[test]
+    fn open_room_is_fully_visible() {
+        let (map, origin) = fixture(&[".....", ".....", "..@..", ".....", "....."]);
+        for algorithm in FovAlgorithm::ALL {
+            let visible = algorithm.compute(&map, origin, 8);
+            assert_eq!(visible.len(), 25, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn range_limits_the_view() {
+        let (map, origin) = fixture(&["@.........."]);
+        for algorithm in FovAlgorithm::ALL {
+            let visible = algorithm.compute(&map, origin, 4);
+            assert!(visible.contains(&Position::new(4, 0)), "{:?}", algorithm);
+            assert!(!visible.contains(&Position::new(5, 0)), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn pillar_casts_a_shadow() {
+        let (map, origin) = fixture(&[
+            "...........",
+            "...........",
+            ".....@.#...",
+            "...........",
+            "...........",
+        ]);
+        for algorithm in FovAlgorithm::ALL {
+            let visible = algorithm.compute(&map, origin, 8);
+            assert!(visible.contains(&Position::new(7, 2)), "{:?}", algorithm);
+            for x in 8..11 {
+                assert!(!visible.contains(&Position::new(x, 2)), "{:?}", algorithm);
+            }
+            // Nothing in front of the pillar is hidden
+            for x in 0..7 {
+                for y in 0..5 {
+                    assert!(visible.contains(&Position::new(x, y)), "{:?}", algorithm);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn corridor_is_seen_end_to_end_with_its_walls() {
+        let (map, origin) = fixture(&["#########", "@........", "#########"]);
+        for algorithm in FovAlgorithm::ALL {
+            let visible = algorithm.compute(&map, origin, 8);
+            for x in 0..9 {
+                assert!(visible.contains(&Position::new(x, 1)), "{:?}", algorithm);
+            }
+            for x in 0..8 {
+                assert!(visible.contains(&Position::new(x, 0)), "{:?}", algorithm);
+                assert!(visible.contains(&Position::new(x, 2)), "{:?}", algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn walls_hide_what_is_behind_them() {
+        let (map, origin) = fixture(&["#####", "#...#", "#.#.#", "#@#.#", "#####"]);
+        for algorithm in FovAlgorithm::ALL {
+            let visible = algorithm.compute(&map, origin, 8);
+            assert!(visible.contains(&Position::new(2, 3)), "{:?}", algorithm);
+            assert!(!visible.contains(&Position::new(3, 1)), "{:?}", algorithm);
+            assert!(!visible.contains(&Position::new(3, 2)), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn diagonal_gap_can_be_seen_through() {
+        let (map, origin) = fixture(&["......", "#.....", "@#...."]);
+        for algorithm in FovAlgorithm::ALL {
+            let visible = algorithm.compute(&map, origin, 8);
+            assert!(visible.contains(&Position::new(1, 1)), "{:?}", algorithm);
+            assert!(visible.contains(&Position::new(2, 2)), "{:?}", algorithm);
+            assert!(!visible.contains(&Position::new(5, 0)), "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn symmetric_shadowcasting_is_symmetric() {
+        let rows = [
+            "#..#....#...",
+            "..#...#.....",
+            ".....##..#..",
+            "#.@.....#...",
+            "...#..#...#.",
+            ".#.....#....",
+            "....#.....##",
+        ];
+        let (map, _) = fixture(&rows);
+        let floors = (0..map.height())
+            .flat_map(|y| (0..map.width()).map(move |x| Position::new(x, y)))
+            .filter(|position| !map.is_opaque_at(position))
+            .collect::<Vec<_>>();
+        let views = floors
+            .iter()
+            .map(|&from| SymmetricShadowcasting.compute(&map, from, 6))
+            .collect::<Vec<_>>();
+        for (a, view_a) in floors.iter().zip(views.iter()) {
+            for (b, view_b) in floors.iter().zip(views.iter()) {
+                assert_eq!(
+                    view_a.contains(b),
+                    view_b.contains(a),
+                    "{:?} and {:?} don't agree",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+}
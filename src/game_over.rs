@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use bevy_ascii_terminal::{StringFormat, Terminal};
+
+use crate::{
+    components::MapViewTerminal,
+    resources::{Depth, GameState, RunStats},
+    VIEWPORT_SIZE,
+};
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::GameOver)
+                .with_system(render_death_screen.after("render_map"))
+                .with_system(handle_game_over_input),
+        );
+    }
+}
+
+fn render_death_screen(
+    mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
+    stats: Res<RunStats>,
+    depth: Res<Depth>,
+) {
+    let mut terminal = terminal.single_mut();
+    let lines = [
+        stats
+            .cause_of_death
+            .clone()
+            .unwrap_or_else(|| "Died".to_string()),
+        String::new(),
+        format!("Turns survived: {}", stats.turns),
+        format!("Kills: {}", stats.total_kills()),
+        format!("Depth reached: {}", depth.0),
+    ];
+    let width = 34;
+    let height = lines.len() as i32 + 5;
+    let x = (VIEWPORT_SIZE[0] as i32 - width) / 2;
+    let y = (VIEWPORT_SIZE[1] as i32 - height) / 2;
+    terminal.clear_box([x, y], [width as u32, height as u32]);
+    terminal.draw_box_double([x, y], [width as u32, height as u32]);
+    terminal.put_string_formatted(
+        [x + 3, y + height - 1],
+        "You are dead",
+        StringFormat::colors(Color::RED, Color::NONE),
+    );
+    for (i, line) in lines.iter().enumerate() {
+        terminal.put_string([x + 2, y + height - 3 - i as i32], line);
+    }
+    terminal.put_string_formatted(
+        [x + 3, y],
+        "ENTER: new run, ESCAPE: menu",
+        StringFormat::colors(Color::YELLOW, Color::NONE),
+    );
+}
+
+/// A new run goes through the setup screen, which throws the old world away once the
+/// new one is ready to be built.
+fn handle_game_over_input(mut input: ResMut<Input<KeyCode>>, mut states: ResMut<State<GameState>>) {
+    let next = if input.just_pressed(KeyCode::Return) {
+        GameState::RunSetup
+    } else if input.just_pressed(KeyCode::Escape) {
+        GameState::MainMenu
+    } else {
+        return;
+    };
+    input.clear();
+    states.set(next).unwrap();
+}
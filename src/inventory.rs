@@ -3,7 +3,8 @@ use bevy_ascii_terminal::{CharFormat, StringFormat, Terminal};
 
 use crate::{
     components::{MapViewTerminal, Player},
-    items::{InBackpack, WantDropItem, WantUseItem},
+    events::PlayerAction,
+    items::InBackpack,
     resources::GameState,
 };
 
@@ -28,19 +29,28 @@ impl Plugin for InventoryPlugin {
     }
 }
 
+/// What `owner` carries, in the order the menus list it. Actions refer to items by their
+/// slot in this list, so it's sorted by name rather than by entity, whose ids depend on
+/// everything spawned before.
+pub fn backpack_items<'a>(
+    owner: Entity,
+    items: impl Iterator<Item = (Entity, &'a InBackpack, &'a Name)>,
+) -> Vec<(Entity, &'a Name)> {
+    let mut items = items
+        .filter(|(_, in_backpack, _)| in_backpack.owner == owner)
+        .map(|(entity, _, name)| (entity, name))
+        .collect::<Vec<_>>();
+    items.sort_by(|(a, a_name), (b, b_name)| a_name.as_str().cmp(b_name.as_str()).then(a.cmp(b)));
+    items
+}
+
 fn handle_inventory_input(
-    backpack: In<HashMap<char, Entity>>,
-    mut commands: Commands,
+    backpack: In<HashMap<char, usize>>,
     input: Res<Input<KeyCode>>,
     mut char_evr: EventReader<ReceivedCharacter>,
     mut states: ResMut<State<GameState>>,
-    player: Query<Entity, With<Player>>,
+    mut actions: EventWriter<PlayerAction>,
 ) {
-    let player = match player.get_single() {
-        Ok(player) => player,
-        Err(_) => return,
-    };
-
     let key = match input.get_just_pressed().next() {
         Some(key) => key,
         None => return,
@@ -50,30 +60,24 @@ fn handle_inventory_input(
         states.pop().unwrap()
     };
 
-    if let Some(&entity) = char_evr
+    if let Some(&slot) = char_evr
         .iter()
         .next()
         .map(|r| r.char)
         .and_then(|c| backpack.0.get(&c))
     {
-        commands.entity(player).insert(WantUseItem { item: entity });
+        actions.send(PlayerAction::UseItem { slot });
         states.pop().unwrap();
     }
 }
 
 fn handle_drop_input(
-    backpack: In<HashMap<char, Entity>>,
-    mut commands: Commands,
+    backpack: In<HashMap<char, usize>>,
     input: Res<Input<KeyCode>>,
     mut char_evr: EventReader<ReceivedCharacter>,
     mut states: ResMut<State<GameState>>,
-    player: Query<Entity, With<Player>>,
+    mut actions: EventWriter<PlayerAction>,
 ) {
-    let player = match player.get_single() {
-        Ok(player) => player,
-        Err(_) => return,
-    };
-
     let key = match input.get_just_pressed().next() {
         Some(key) => key,
         None => return,
@@ -83,15 +87,13 @@ fn handle_drop_input(
         states.pop().unwrap()
     };
 
-    if let Some(&entity) = char_evr
+    if let Some(&slot) = char_evr
         .iter()
         .next()
         .map(|r| r.char)
         .and_then(|c| backpack.0.get(&c))
     {
-        commands
-            .entity(player)
-            .insert(WantDropItem { item: entity });
+        actions.send(PlayerAction::DropItem { slot });
         states.pop().unwrap();
     }
 }
@@ -99,53 +101,33 @@ fn handle_drop_input(
 fn render_inventory(
     mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
     player: Query<Entity, With<Player>>,
-    backpack: Query<(Entity, &Name, &InBackpack)>,
-) -> HashMap<char, Entity> {
+    backpack: Query<(Entity, &InBackpack, &Name)>,
+) -> HashMap<char, usize> {
     let terminal = terminal.single_mut();
-    let player = player.get_single().ok();
-    let backpack = backpack
-        .iter()
-        .filter_map(|(entity, name, in_backpack)| {
-            let player = player?;
-            if player == in_backpack.owner {
-                Some((entity, name))
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+    let backpack = match player.get_single() {
+        Ok(player) => backpack_items(player, backpack.iter()),
+        Err(_) => Vec::new(),
+    };
 
     draw_items_menu("Inventory", &backpack, terminal);
 
-    ('a'..'z')
-        .zip(backpack.into_iter().map(|(entity, _)| entity))
-        .collect()
+    ('a'..'z').zip(0..backpack.len()).collect()
 }
 
 fn render_drop_menu(
     mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
     player: Query<Entity, With<Player>>,
-    backpack: Query<(Entity, &Name, &InBackpack)>,
-) -> HashMap<char, Entity> {
+    backpack: Query<(Entity, &InBackpack, &Name)>,
+) -> HashMap<char, usize> {
     let terminal = terminal.single_mut();
-    let player = player.get_single().ok();
-    let backpack = backpack
-        .iter()
-        .filter_map(|(entity, name, in_backpack)| {
-            let player = player?;
-            if player == in_backpack.owner {
-                Some((entity, name))
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+    let backpack = match player.get_single() {
+        Ok(player) => backpack_items(player, backpack.iter()),
+        Err(_) => Vec::new(),
+    };
 
     draw_items_menu("Drop item", &backpack, terminal);
 
-    ('a'..'z')
-        .zip(backpack.into_iter().map(|(entity, _)| entity))
-        .collect()
+    ('a'..'z').zip(0..backpack.len()).collect()
 }
 
 fn draw_items_menu(title: &str, backpack: &[(Entity, &Name)], mut terminal: Mut<Terminal>) {
@@ -1,12 +1,13 @@
 use bevy::prelude::*;
-use bevy_ascii_terminal::Tile;
 
 use crate::{
     combat::Health,
     components::{Energy, Layer, Position, Unrevealable},
     events::{PickupEvent, WantPickup},
+    light::Lantern,
     log::GameLog,
     map::Map,
+    raws::ItemDef,
     LAYER_ITEM,
 };
 
@@ -18,6 +19,12 @@ pub struct Potion {
     pub heal_amount: u32,
 }
 
+/// Lamp oil, poured into the user's lantern.
+#[derive(Component)]
+pub struct Fuel {
+    pub amount: u32,
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct InBackpack {
     pub owner: Entity,
@@ -45,21 +52,21 @@ impl Plugin for ItemPlugin {
     }
 }
 
-pub fn health_potion(commands: &mut Commands, position: Position) -> Entity {
-    commands
-        .spawn()
-        .insert(Item)
-        .insert(Potion { heal_amount: 8 })
-        .insert(Tile {
-            glyph: '¡',
-            fg_color: Color::YELLOW,
-            bg_color: Color::NONE,
-        })
-        .insert(Name::new("Healing potion"))
+pub fn spawn_item(commands: &mut Commands, def: &ItemDef, position: Position) -> Entity {
+    let mut item = commands.spawn();
+    item.insert(Item)
+        .insert(def.tile)
+        .insert(Name::new(def.name.clone()))
         .insert(position)
         .insert(Layer(LAYER_ITEM))
-        .insert(Unrevealable)
-        .id()
+        .insert(Unrevealable);
+    if let Some(heal_amount) = def.effects.heal {
+        item.insert(Potion { heal_amount });
+    }
+    if let Some(amount) = def.effects.fuel {
+        item.insert(Fuel { amount });
+    }
+    item.id()
 }
 
 pub fn handle_want_pickup(
@@ -108,17 +115,31 @@ pub fn handle_pickup(
 fn handle_use_item(
     mut commands: Commands,
     mut game_log: ResMut<GameLog>,
-    mut to_use: Query<(Entity, &WantUseItem, &Name, Option<&mut Health>)>,
-    items: Query<&Potion>,
+    mut to_use: Query<(
+        Entity,
+        &WantUseItem,
+        &Name,
+        Option<&mut Health>,
+        Option<&mut Lantern>,
+    )>,
+    potions: Query<&Potion>,
+    fuels: Query<&Fuel>,
 ) {
-    for (entity, to_use, name, health) in to_use.iter_mut() {
-        if let Some((potion, mut health)) = items.get(to_use.item).ok().zip(health) {
+    for (entity, to_use, name, health, lantern) in to_use.iter_mut() {
+        if let Some((potion, mut health)) = potions.get(to_use.item).ok().zip(health) {
             health.current = health.max.min(health.current + potion.heal_amount);
             commands.entity(to_use.item).remove::<InBackpack>();
             game_log.push(format!(
                 "{} drinks potion, restores: {} hp",
                 name, potion.heal_amount
             ));
+        } else if let Some((fuel, mut lantern)) = fuels.get(to_use.item).ok().zip(lantern) {
+            lantern.fuel = lantern.max_fuel.min(lantern.fuel + fuel.amount);
+            commands.entity(to_use.item).remove::<InBackpack>();
+            game_log.push(format!(
+                "{} refills the lantern, fuel: {}/{}",
+                name, lantern.fuel, lantern.max_fuel
+            ));
         }
 
         commands.entity(entity).remove::<WantUseItem>();
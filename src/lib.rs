@@ -0,0 +1,528 @@
+#![allow(clippy::type_complexity)]
+pub mod combat;
+pub mod components;
+pub mod door;
+pub mod dungeon;
+pub mod events;
+pub mod fov;
+pub mod game_over;
+pub mod inventory;
+pub mod items;
+pub mod light;
+pub mod log;
+pub mod map;
+pub mod map_builders;
+pub mod map_tile;
+pub mod memory;
+pub mod menu;
+pub mod monster;
+pub mod morgue;
+pub mod player;
+pub mod raws;
+pub mod replay;
+pub mod resources;
+pub mod rng;
+pub mod save;
+pub mod settings;
+pub mod side_panel;
+pub mod spawn_table;
+pub mod terrain;
+pub mod turn;
+pub mod utils;
+pub mod viewport;
+
+use crate::components::*;
+use bevy::prelude::*;
+use bevy_ascii_terminal::{Pivot, StringFormat, Terminal, TerminalBundle, TerminalPlugin, Tile};
+use bevy_inspector_egui::{WorldInspectorParams, WorldInspectorPlugin};
+use bevy_tiled_camera::{TiledCameraBundle, TiledCameraPlugin};
+use combat::{CombatPlugin, Health};
+use door::DoorPlugin;
+use events::{
+    ActionTakenEvent, AttackEvent, MoveEvent, OpenDoorEvent, PlayerAction, WantCloseDoor,
+    WantPickup, WantTakeStairs, WantToggleLantern,
+};
+use fov::FovSettings;
+use game_over::GameOverPlugin;
+use inventory::{backpack_items, InventoryPlugin};
+use items::{InBackpack, ItemPlugin, WantDropItem, WantUseItem};
+use itertools::Itertools;
+use light::{LightMap, LightPlugin};
+use log::GameLog;
+use map::{Map, MapPlugin};
+use map_tile::{Stairs, TileType};
+use memory::{Memory, MemoryPlugin};
+use menu::MenuPlugin;
+use monster::MonsterPlugin;
+use morgue::MorguePlugin;
+use raws::Raws;
+use replay::{Replay, ReplayPlugin};
+use resources::GameState;
+use rng::GameRng;
+use save::SavePlugin;
+use settings::MapSettings;
+use side_panel::{render_player_stats, render_visible_entities};
+use terrain::TerrainPlugin;
+use turn::{PlayerTurn, TurnPlugin};
+use utils::{clear_undercursor, cursor_hint, Grayscale, Shade, UnderCursor};
+use viewport::{Viewport, ViewportPlugin};
+
+const LAYER_MAP: u32 = 0;
+const LAYER_ITEM: u32 = 2;
+const LAYER_MONSTER: u32 = 3;
+const LAYER_PLAYER: u32 = 4;
+
+const WINDOW_SIZE: [u32; 2] = [80, 45];
+const LOG_PANEL_SIZE: [u32; 2] = [80, 6];
+const STATUS_PANEL_SIZE: [u32; 2] = [22, WINDOW_SIZE[1] - LOG_PANEL_SIZE[1]];
+pub const VIEWPORT_SIZE: [u32; 2] = [
+    WINDOW_SIZE[0] - STATUS_PANEL_SIZE[0],
+    WINDOW_SIZE[1] - LOG_PANEL_SIZE[1],
+];
+/// Default size of a level, which scrolls through the viewport when it's larger.
+pub const MAP_SIZE: [u32; 2] = [96, 64];
+/// Smallest level the builders can work with: room for three of the smallest rooms
+/// across, plus the outer walls.
+pub const MIN_MAP_SIZE: u32 = 3 * 4 + 2;
+
+/// Everything the game adds on top of bevy's `DefaultPlugins`.
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        let raws = Raws::from_env().unwrap_or_else(|err| {
+            eprintln!("Failed to load monster and item definitions: {}", err);
+            std::process::exit(1);
+        });
+
+        app.add_state::<GameState>(GameState::MainMenu)
+            .add_event::<AttackEvent>()
+            .add_event::<MoveEvent>()
+            .add_event::<PlayerAction>()
+            .add_event::<ActionTakenEvent>()
+            .init_resource::<GameLog>()
+            .insert_resource(GameRng::from_env())
+            .insert_resource(MapSettings::from_env())
+            .insert_resource(FovSettings::from_env())
+            .insert_resource(raws)
+            .add_plugin(WorldInspectorPlugin::new())
+            .add_plugin(TerminalPlugin)
+            .add_plugin(TiledCameraPlugin)
+            .add_plugin(MapPlugin)
+            .add_plugin(ItemPlugin)
+            .add_plugin(TurnPlugin)
+            .add_plugin(MonsterPlugin)
+            .add_plugin(CombatPlugin)
+            .add_plugin(InventoryPlugin)
+            .add_plugin(DoorPlugin)
+            .add_plugin(ViewportPlugin)
+            .add_plugin(TerrainPlugin)
+            .add_plugin(LightPlugin)
+            .add_plugin(MemoryPlugin)
+            .add_plugin(SavePlugin)
+            .add_plugin(GameOverPlugin)
+            .add_plugin(MenuPlugin)
+            .add_plugin(ReplayPlugin)
+            .add_plugin(MorguePlugin)
+            .add_startup_system(setup_camera)
+            .add_system(update_fov)
+            .add_system(update_visibility.after(update_fov))
+            .add_system(render_map.after(update_visibility).label("render_map"))
+            .add_system(render_player_stats.chain(render_visible_entities))
+            .add_system(render_log_panel.chain(render_hint_text))
+            .add_system(toggle_inspector)
+            .add_system_to_stage(CoreStage::First, clear_undercursor)
+            .add_system_to_stage(CoreStage::PreUpdate, cursor_hint);
+    }
+}
+
+fn toggle_inspector(
+    input: ResMut<Input<KeyCode>>,
+    mut inspector_params: ResMut<WorldInspectorParams>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        inspector_params.enabled = !inspector_params.enabled;
+    }
+}
+
+fn setup_camera(mut commands: Commands) {
+    let mut map_terminal = TerminalBundle::new().with_size(VIEWPORT_SIZE);
+    map_terminal.renderer.terminal_pivot.0 = Vec2::new(1.0, 1.0);
+    map_terminal.transform.translation = Vec3::new(
+        WINDOW_SIZE[0] as f32 / 2.0,
+        WINDOW_SIZE[1] as f32 / 2.0,
+        0.0,
+    );
+    commands.spawn_bundle(map_terminal).insert(MapViewTerminal);
+
+    let mut status_terminal = TerminalBundle::new().with_size(STATUS_PANEL_SIZE);
+    status_terminal.renderer.terminal_pivot.0 = Vec2::new(0.0, 1.0);
+    status_terminal.transform.translation = Vec3::new(
+        WINDOW_SIZE[0] as f32 / -2.0,
+        WINDOW_SIZE[1] as f32 / 2.0,
+        0.0,
+    );
+    commands
+        .spawn_bundle(status_terminal)
+        .insert(StatusTerminal);
+
+    let mut logs_terminal = TerminalBundle::new().with_size(LOG_PANEL_SIZE);
+    logs_terminal.renderer.terminal_pivot.0 = Vec2::new(0.0, 0.0);
+    logs_terminal.transform.translation = Vec3::new(
+        WINDOW_SIZE[0] as f32 / -2.0,
+        WINDOW_SIZE[1] as f32 / -2.0,
+        0.0,
+    );
+    commands.spawn_bundle(logs_terminal).insert(LogTerminal);
+
+    commands.spawn_bundle(
+        TiledCameraBundle::new()
+            .with_centered(true)
+            .with_pixels_per_tile(8)
+            .with_tile_count(WINDOW_SIZE),
+    );
+}
+
+fn render_map(
+    tiles: Query<
+        (
+            &Tile,
+            &Position,
+            Option<&Visible>,
+            Option<&Layer>,
+            Option<&UnderCursor>,
+        ),
+        Or<(With<Revealed>, With<Visible>)>,
+    >,
+    mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
+    visible_entities: Query<(), With<Visible>>,
+    viewport: Res<Viewport>,
+    light_map: Res<LightMap>,
+    memory: Res<Memory>,
+) {
+    let mut terminal = terminal.single_mut();
+    terminal.clear();
+    // Monsters and items out of sight are drawn where they were last seen
+    let ghosts = memory
+        .ghosts()
+        .filter(|&(entity, _)| entity.map_or(true, |entity| visible_entities.get(entity).is_err()))
+        .filter_map(|(_, ghost)| {
+            let screen = viewport.world_to_screen(&ghost.position)?;
+            Some((&ghost.tile, screen, None, Some(&ghost.layer), None))
+        });
+    let sorted_tiles = tiles
+        .iter()
+        .filter_map(|(tile, position, visible, layer, under_cursor)| {
+            let screen = viewport.world_to_screen(position)?;
+            let light = light_map.level(position);
+            Some((tile, screen, visible.map(|_| light), layer, under_cursor))
+        })
+        .chain(ghosts)
+        .sorted_by(|a, b| a.3.cmp(&b.3))
+        .map(|(tile, screen, visible, _, under_cursor)| (tile, screen, visible, under_cursor))
+        .map(|(tile, screen, visible, under_cursor)| {
+            let tile = if under_cursor.is_some() {
+                Tile {
+                    glyph: tile.glyph,
+                    fg_color: Color::BLACK,
+                    bg_color: Color::YELLOW,
+                }
+            } else if let Some(light) = visible {
+                tile.shaded(light)
+            } else {
+                tile.grayscale()
+            };
+            (tile, screen)
+        });
+
+    for (tile, screen) in sorted_tiles {
+        terminal.put_tile(screen, tile);
+    }
+}
+
+fn keyboard_handling(
+    mut input: ResMut<Input<KeyCode>>,
+    mut states: ResMut<State<GameState>>,
+    mut actions: EventWriter<PlayerAction>,
+    replay: Option<Res<Replay>>,
+    turn: PlayerTurn,
+) {
+    // A replay plays the turns itself
+    if replay.is_some() || !turn.is_waiting() {
+        return;
+    }
+
+    let key = match input.get_just_pressed().next() {
+        Some(key) => key,
+        None => return,
+    };
+
+    let action = match key {
+        KeyCode::Numpad8 | KeyCode::Up => PlayerAction::Move { dx: 0, dy: 1 },
+        KeyCode::Numpad2 | KeyCode::Down => PlayerAction::Move { dx: 0, dy: -1 },
+        KeyCode::Numpad4 | KeyCode::Left => PlayerAction::Move { dx: -1, dy: 0 },
+        KeyCode::Numpad6 | KeyCode::Right => PlayerAction::Move { dx: 1, dy: 0 },
+        KeyCode::Numpad7 => PlayerAction::Move { dx: -1, dy: 1 },
+        KeyCode::Numpad9 => PlayerAction::Move { dx: 1, dy: 1 },
+        KeyCode::Numpad1 => PlayerAction::Move { dx: -1, dy: -1 },
+        KeyCode::Numpad3 => PlayerAction::Move { dx: 1, dy: -1 },
+        KeyCode::Numpad5 => PlayerAction::Wait,
+        KeyCode::G => PlayerAction::PickUp,
+        KeyCode::C => PlayerAction::CloseDoor,
+        KeyCode::L => PlayerAction::ToggleLantern,
+        KeyCode::Period => PlayerAction::Descend,
+        KeyCode::Comma => PlayerAction::Ascend,
+        KeyCode::I => {
+            input.clear();
+            states.push(GameState::Inventory).unwrap();
+            return;
+        }
+        KeyCode::D => {
+            input.clear();
+            states.push(GameState::DropItemMenu).unwrap();
+            return;
+        }
+        _ => return,
+    };
+    actions.send(action);
+}
+
+/// Turns the action picked this frame into what the player wants to do on their turn.
+fn perform_player_action(
+    mut commands: Commands,
+    mut actions: EventReader<PlayerAction>,
+    mut taken_events: EventWriter<ActionTakenEvent>,
+    mut players: Query<(Entity, &Position, &mut Energy), (With<Player>, With<TakingATurn>)>,
+    backpack: Query<(Entity, &InBackpack, &Name)>,
+) {
+    let action = match actions.iter().last() {
+        Some(action) => *action,
+        None => return,
+    };
+    let (player, &player_pos, mut energy) = match players.get_single_mut() {
+        Ok(players) => players,
+        Err(_) => return,
+    };
+    taken_events.send(ActionTakenEvent(action));
+
+    match action {
+        PlayerAction::Move { dx, dy } => {
+            energy.0 = 0;
+            commands.entity(player).insert(WantToMove {
+                position: Position::new(player_pos.x + dx, player_pos.y + dy),
+            });
+        }
+        PlayerAction::Wait => energy.0 = 0,
+        PlayerAction::PickUp => {
+            commands.entity(player).insert(WantPickup);
+        }
+        PlayerAction::CloseDoor => {
+            commands.entity(player).insert(WantCloseDoor);
+        }
+        PlayerAction::ToggleLantern => {
+            commands.entity(player).insert(WantToggleLantern);
+        }
+        PlayerAction::Descend => {
+            commands.entity(player).insert(WantTakeStairs(Stairs::Down));
+        }
+        PlayerAction::Ascend => {
+            commands.entity(player).insert(WantTakeStairs(Stairs::Up));
+        }
+        PlayerAction::UseItem { slot } => {
+            if let Some(&(item, _)) = backpack_items(player, backpack.iter()).get(slot) {
+                commands.entity(player).insert(WantUseItem { item });
+            }
+        }
+        PlayerAction::DropItem { slot } => {
+            if let Some(&(item, _)) = backpack_items(player, backpack.iter()).get(slot) {
+                commands.entity(player).insert(WantDropItem { item });
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn handle_want_to_move(
+    mut commands: Commands,
+    mut attack_events: EventWriter<AttackEvent>,
+    mut move_events: EventWriter<MoveEvent>,
+    mut open_door_events: EventWriter<OpenDoorEvent>,
+    mut game_log: ResMut<GameLog>,
+    mut rng: ResMut<GameRng>,
+    map: Res<Map>,
+    mut actors: Query<(
+        Entity,
+        &WantToMove,
+        &Position,
+        &mut Energy,
+        &Name,
+        Option<&Visible>,
+    )>,
+    blocks: Query<Entity, With<Blocker>>,
+    victims: Query<Entity, With<Health>>,
+    tiles: Query<&TileType>,
+) {
+    for (entity, to_move, position, mut energy, name, visible) in actors.iter_mut() {
+        if !map.is_in_bounds(&to_move.position) {
+            continue;
+        }
+
+        let at_position = map.at_position(&to_move.position);
+
+        let victim = at_position
+            .iter()
+            .find(|&&e| victims.get(e).ok().is_some())
+            .cloned();
+
+        if let Some(victim) = victim {
+            attack_events.send(AttackEvent {
+                attacker: entity,
+                target: victim,
+            });
+            commands.entity(entity).remove::<WantToMove>();
+            continue;
+        }
+
+        let closed_door = at_position
+            .iter()
+            .find(|&&e| tiles.get(e).ok() == Some(&TileType::Door { open: false }))
+            .cloned();
+
+        if let Some(door) = closed_door {
+            open_door_events.send(OpenDoorEvent {
+                opener: entity,
+                door,
+            });
+            commands.entity(entity).remove::<WantToMove>();
+            continue;
+        }
+
+        let can_move = at_position.iter().all(|&e| blocks.get(e).ok().is_none());
+
+        let slip_chance = map
+            .tile_type(position)
+            .map_or(0, |tile_type| tile_type.slip_chance());
+        if can_move && slip_chance > 0 && rng.range(0, 100) < slip_chance {
+            if visible.is_some() {
+                game_log.push(format!("{} slips and loses their footing", name));
+            }
+            energy.0 = 0;
+        } else if can_move {
+            move_events.send(MoveEvent {
+                entity,
+                position: to_move.position,
+            });
+        }
+        commands.entity(entity).remove::<WantToMove>();
+    }
+}
+
+pub fn movement(
+    mut move_events: EventReader<MoveEvent>,
+    map: Res<Map>,
+    mut actors: Query<(&mut Position, &mut Energy)>,
+) {
+    for event in move_events.iter() {
+        if let Ok((mut position, mut energy)) = actors.get_mut(event.entity) {
+            *position = event.position;
+            // Rough terrain takes longer to cross, which shows up as an energy debt
+            let cost = map
+                .tile_type(&event.position)
+                .map_or(100, |tile_type| tile_type.move_cost());
+            energy.0 = 100 - cost;
+        }
+    }
+}
+
+/// Marks what the player can see: tiles in their field of view that are lit well enough,
+/// and whatever stands right where they are.
+pub fn update_visibility(
+    mut commands: Commands,
+    map: Res<Map>,
+    light_map: Res<LightMap>,
+    fov: Query<(&Fov, &Position, ChangeTrackers<Fov>), With<Player>>,
+    visible: Query<Entity, With<Visible>>,
+    unrevealable: Query<Entity, With<Unrevealable>>,
+) {
+    if let Ok((fov, player_pos, fov_tracker)) = fov.get_single() {
+        if !fov_tracker.is_changed() && !light_map.is_changed() {
+            return;
+        }
+        for entity in visible.iter() {
+            commands.entity(entity).remove::<Visible>();
+        }
+        let seen = fov
+            .visible_tiles
+            .iter()
+            .filter(|&position| position == player_pos || light_map.is_lit(position));
+        for visible in seen {
+            for entity in map.at_position(visible) {
+                commands.entity(entity).insert(Visible);
+                if unrevealable.get(entity).is_err() {
+                    commands.entity(entity).insert(Revealed);
+                }
+            }
+        }
+    }
+}
+
+pub fn update_fov(
+    map: Res<Map>,
+    settings: Res<FovSettings>,
+    mut units: Query<(&mut Fov, &Position, ChangeTrackers<Position>)>,
+    changed_tiles: Query<Entity, Changed<TileType>>,
+) {
+    // Opening or closing a door changes what everyone can see, even if they stand still
+    let map_changed = !changed_tiles.is_empty();
+    for (mut fov, position, position_tracker) in units.iter_mut() {
+        if !map_changed && !position_tracker.is_changed() {
+            continue;
+        }
+        let algorithm = fov.algorithm.unwrap_or(settings.algorithm);
+        fov.visible_tiles = algorithm.compute(&map, *position, fov.range);
+    }
+}
+
+fn render_log_panel(
+    mut terminal: Query<&mut Terminal, With<LogTerminal>>,
+    game_log: Res<GameLog>,
+    rng: Res<GameRng>,
+) {
+    if let Ok(mut terminal) = terminal.get_single_mut() {
+        terminal.clear();
+        terminal.draw_box_single([0, 0], LOG_PANEL_SIZE);
+
+        let seed = format!(" Seed: {} ", rng.seed());
+        let x = LOG_PANEL_SIZE[0] as i32 - seed.len() as i32 - 2;
+        terminal.put_string([x, 0], &seed);
+
+        let count = (LOG_PANEL_SIZE[1] - 2) as usize;
+        game_log
+            .entries()
+            .iter()
+            .rev()
+            .take(count)
+            .rev()
+            .enumerate()
+            .for_each(|(i, log)| {
+                terminal.put_string([2, (count - i) as i32], log);
+            });
+    }
+}
+
+fn render_hint_text(
+    mut terminal: Query<&mut Terminal, With<LogTerminal>>,
+    highlighted: Query<&Name, With<UnderCursor>>,
+) {
+    if let Some((mut terminal, name)) = terminal
+        .get_single_mut()
+        .ok()
+        .zip(highlighted.get_single().ok())
+    {
+        let text = format!("You see {}", name);
+        let x = 3;
+        let y = terminal.height() as i32 - 1;
+        let format = StringFormat::default().with_pivot(Pivot::BottomRight);
+        terminal.put_string_formatted([x, y], &text, format);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use bracket_lib::prelude::{DistanceAlg, Point};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{Player, Position, TakingATurn},
+    events::WantToggleLantern,
+    fov::FovSettings,
+    log::GameLog,
+    map::Map,
+    update_fov, update_visibility,
+};
+
+/// Light reaching a tile with no light source around.
+pub const AMBIENT_LIGHT: f32 = 0.0;
+/// Tiles darker than this can't be seen, even in line of sight.
+pub const MIN_VISIBLE_LIGHT: f32 = 0.05;
+
+/// Something that lights up the tiles around it: a torch on a wall, the player's lantern
+/// or a glowing monster.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LightSource {
+    pub range: u32,
+    pub intensity: f32,
+}
+
+/// The player's lantern. It burns a unit of fuel every turn it's lit.
+#[derive(Component, Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Lantern {
+    pub fuel: u32,
+    pub max_fuel: u32,
+    pub range: u32,
+    pub lit: bool,
+}
+
+impl Default for Lantern {
+    fn default() -> Self {
+        Self {
+            fuel: 500,
+            max_fuel: 500,
+            range: 6,
+            lit: true,
+        }
+    }
+}
+
+impl Lantern {
+    pub fn is_burning(&self) -> bool {
+        self.lit && self.fuel > 0
+    }
+
+    pub fn light(&self) -> LightSource {
+        LightSource {
+            range: self.range,
+            intensity: 1.0,
+        }
+    }
+}
+
+/// How much light reaches every tile of the current level, from 0 (pitch dark) to 1.
+#[derive(Default, PartialEq)]
+pub struct LightMap {
+    width: usize,
+    height: usize,
+    levels: Vec<f32>,
+}
+
+impl LightMap {
+    pub fn level(&self, position: &Position) -> f32 {
+        if position.x < 0
+            || position.y < 0
+            || position.x >= self.width as i32
+            || position.y >= self.height as i32
+        {
+            return AMBIENT_LIGHT;
+        }
+        self.levels[position.y as usize * self.width + position.x as usize]
+    }
+
+    pub fn is_lit(&self, position: &Position) -> bool {
+        self.level(position) >= MIN_VISIBLE_LIGHT
+    }
+}
+
+pub struct LightPlugin;
+
+impl Plugin for LightPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LightMap>()
+            .add_system(toggle_lantern)
+            .add_system(burn_lantern)
+            .add_system(
+                update_lighting
+                    .after(update_fov)
+                    .after(toggle_lantern)
+                    .after(burn_lantern)
+                    .before(update_visibility),
+            );
+    }
+}
+
+/// What every light source reached the last time its field of view was worked out,
+/// and the map it was worked out on. Lanterns are kept apart since an entity could carry
+/// both.
+#[derive(Default)]
+struct LitTiles {
+    opacity_revision: u64,
+    lights: HashMap<Entity, HashSet<Position>>,
+    lanterns: HashMap<Entity, HashSet<Position>>,
+}
+
+/// Sums up the light of every source over the tiles it can reach, using the global
+/// field of view algorithm to tell which ones those are. A source's field of view is only
+/// worked out again when it moves or the map's opacity changes, and the sum only when
+/// something changed, so the torches cost nothing while the level stays the same.
+#[allow(clippy::too_many_arguments)]
+fn update_lighting(
+    mut light_map: ResMut<LightMap>,
+    mut lit: Local<LitTiles>,
+    map: Res<Map>,
+    fov_settings: Res<FovSettings>,
+    lights: Query<(
+        Entity,
+        &LightSource,
+        &Position,
+        ChangeTrackers<LightSource>,
+        ChangeTrackers<Position>,
+    )>,
+    lanterns: Query<(
+        Entity,
+        &Lantern,
+        &Position,
+        ChangeTrackers<Lantern>,
+        ChangeTrackers<Position>,
+    )>,
+    removed_lights: RemovedComponents<LightSource>,
+    removed_lanterns: RemovedComponents<Lantern>,
+) {
+    // Opening a door or a new level changes what every light reaches. The map is indexed
+    // at the end of the frame, so its revision is what says when it caught up
+    let map_changed = lit.opacity_revision != map.opacity_revision() || fov_settings.is_changed();
+    if map_changed {
+        lit.opacity_revision = map.opacity_revision();
+        lit.lights.clear();
+        lit.lanterns.clear();
+    }
+    let mut dirty = map_changed;
+    for entity in removed_lights.iter() {
+        dirty |= lit.lights.remove(&entity).is_some();
+    }
+    for entity in removed_lanterns.iter() {
+        dirty |= lit.lanterns.remove(&entity).is_some();
+    }
+
+    for (entity, light, position, light_tracker, position_tracker) in lights.iter() {
+        dirty |= light_tracker.is_changed();
+        if position_tracker.is_changed()
+            || light_tracker.is_changed()
+            || !lit.lights.contains_key(&entity)
+        {
+            let tiles = fov_settings.algorithm.compute(&map, *position, light.range);
+            lit.lights.insert(entity, tiles);
+            dirty = true;
+        }
+    }
+    for (entity, lantern, position, lantern_tracker, position_tracker) in lanterns.iter() {
+        dirty |= lantern_tracker.is_changed();
+        if !lantern.is_burning() {
+            dirty |= lit.lanterns.remove(&entity).is_some();
+        } else if position_tracker.is_changed() || !lit.lanterns.contains_key(&entity) {
+            let tiles = fov_settings
+                .algorithm
+                .compute(&map, *position, lantern.light().range);
+            lit.lanterns.insert(entity, tiles);
+            dirty = true;
+        }
+    }
+    if !dirty {
+        return;
+    }
+
+    let width = map.width();
+    let height = map.height();
+    let mut levels = vec![AMBIENT_LIGHT; width * height];
+    let all_lights = lights
+        .iter()
+        .filter_map(|(entity, &light, position, _, _)| {
+            lit.lights
+                .get(&entity)
+                .map(|tiles| (light, position, tiles))
+        })
+        .chain(
+            lanterns
+                .iter()
+                .filter_map(|(entity, lantern, position, _, _)| {
+                    lit.lanterns
+                        .get(&entity)
+                        .map(|tiles| (lantern.light(), position, tiles))
+                }),
+        );
+    for (light, position, lit_tiles) in all_lights {
+        let origin = Point::from(position);
+        for point in lit_tiles.iter().map(Point::from) {
+            let distance = DistanceAlg::Pythagoras.distance2d(origin, point);
+            let falloff = (1.0 - distance / (light.range as f32 + 1.0)).max(0.0);
+            let level = &mut levels[point.y as usize * width + point.x as usize];
+            *level = (*level + light.intensity * falloff).min(1.0);
+        }
+    }
+
+    // Only written back when something changed so visibility isn't redone for nothing
+    let new_map = LightMap {
+        width,
+        height,
+        levels,
+    };
+    if *light_map != new_map {
+        *light_map = new_map;
+    }
+}
+
+fn toggle_lantern(
+    mut commands: Commands,
+    mut game_log: ResMut<GameLog>,
+    mut lanterns: Query<(Entity, &mut Lantern), With<WantToggleLantern>>,
+) {
+    for (entity, mut lantern) in lanterns.iter_mut() {
+        commands.entity(entity).remove::<WantToggleLantern>();
+        if lantern.fuel == 0 {
+            game_log.push("Your lantern has no fuel left");
+            continue;
+        }
+        lantern.lit = !lantern.lit;
+        if lantern.lit {
+            game_log.push("You light your lantern");
+        } else {
+            game_log.push("You put out your lantern");
+        }
+    }
+}
+
+fn burn_lantern(
+    mut game_log: ResMut<GameLog>,
+    mut lanterns: Query<&mut Lantern, (With<Player>, Added<TakingATurn>)>,
+) {
+    const LOW_FUEL: u32 = 50;
+
+    for mut lantern in lanterns.iter_mut() {
+        if !lantern.is_burning() {
+            continue;
+        }
+        lantern.fuel -= 1;
+        if lantern.fuel == 0 {
+            game_log.push("Your lantern sputters and goes out");
+        } else if lantern.fuel == LOW_FUEL {
+            game_log.push("Your lantern flickers, it's running low on fuel");
+        }
+    }
+}
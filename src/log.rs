@@ -7,6 +7,10 @@ impl GameLog {
         Self { entries: vec![] }
     }
 
+    pub fn with_entries(entries: Vec<String>) -> Self {
+        Self { entries }
+    }
+
     pub fn push<T: Into<String>>(&mut self, entry: T) {
         self.entries.push(entry.into());
     }
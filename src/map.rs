@@ -1,34 +1,88 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
 use bevy::prelude::*;
+use bevy_ascii_terminal::Tile;
 use bracket_lib::prelude::{
-    Algorithm2D, BaseMap, DistanceAlg, FastNoise, FractalType, NoiseType, Point,
-    RandomNumberGenerator, Rect, SmallVec,
+    Algorithm2D, BaseMap, DistanceAlg, Point, RandomNumberGenerator, SmallVec,
 };
 
 use crate::{
-    items::health_potion, map_tile::TileType, monster::spawn_monster, player::spawn_player,
-    Blocker, Opaque, Position, MAP_SIZE,
+    combat::{Attack, Health},
+    components::{Energy, Fov, Player, Revealed, Speed},
+    dungeon::{Dungeon, StoredItem, StoredLevel, StoredMonster, StoredTile},
+    events::{ChangeLevelEvent, MapReplacedEvent, NewRunEvent, WantTakeStairs},
+    items::{Fuel, InBackpack, Item, Potion},
+    light::LightSource,
+    log::GameLog,
+    map_tile::{Stairs, TileType},
+    memory::Memory,
+    monster::Monster,
+    player::spawn_player,
+    raws::{spawn_named, Raws},
+    resources::{Depth, GameState, RunStats},
+    rng::GameRng,
+    settings::MapSettings,
+    Blocker, Opaque, Position,
 };
 
+/// How an entity on the map affects movement and sight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapEntry {
+    pub position: Position,
+    /// Terrain goes into the dense grid, everything else is kept per occupied position
+    pub tile_type: Option<TileType>,
+    pub blocks: bool,
+    pub opaque: bool,
+    pub closed_door: bool,
+}
+
+impl MapEntry {
+    pub fn new(
+        position: Position,
+        tile_type: Option<&TileType>,
+        blocker: Option<&Blocker>,
+        opaque: Option<&Opaque>,
+    ) -> Self {
+        Self {
+            position,
+            tile_type: tile_type.copied(),
+            blocks: blocker.is_some(),
+            opaque: opaque.is_some(),
+            closed_door: tile_type == Some(&TileType::Door { open: false }),
+        }
+    }
+}
+
+/// Spatial index of every positioned entity on the current level.
+/// It's kept up to date as entities spawn, move and go away, see [`index_map`].
 #[allow(dead_code)]
 pub struct Map {
     width: usize,
     height: usize,
-    pub tiles: HashMap<Position, Vec<Entity>>,
-    pub opaque: HashSet<Position>,
-    pub blockers: HashSet<Position>,
+    terrain: Vec<Option<Entity>>,
+    occupants: HashMap<Position, Vec<Entity>>,
+    entries: HashMap<Entity, MapEntry>,
+    opaque: Vec<bool>,
+    blocked: Vec<bool>,
+    /// Closed doors block movement, but anyone can open them, so pathing goes through
+    closed_doors: Vec<bool>,
+    /// Bumped whenever a cell turns opaque or clear, see [`Map::opacity_revision`]
+    opacity_revision: u64,
 }
 
 #[allow(dead_code)]
 impl Map {
-    fn new(width: usize, height: usize) -> Self {
+    pub fn new(width: usize, height: usize) -> Self {
         Self {
             width,
             height,
-            tiles: HashMap::new(),
-            opaque: HashSet::new(),
-            blockers: HashSet::new(),
+            terrain: vec![None; width * height],
+            occupants: HashMap::new(),
+            entries: HashMap::new(),
+            opaque: vec![false; width * height],
+            blocked: vec![false; width * height],
+            closed_doors: vec![false; width * height],
+            opacity_revision: 0,
         }
     }
 
@@ -47,8 +101,127 @@ impl Map {
         self.height
     }
 
+    fn position_idx(&self, position: &Position) -> Option<usize> {
+        if self.is_in_bounds(position) {
+            Some(position.y as usize * self.width + position.x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The terrain at `position` first, then whatever stands on it.
     pub fn at_position(&self, position: &Position) -> Vec<Entity> {
-        self.tiles.get(position).cloned().unwrap_or_default()
+        let terrain = self
+            .position_idx(position)
+            .and_then(|idx| self.terrain[idx]);
+        terrain
+            .into_iter()
+            .chain(self.occupants.get(position).into_iter().flatten().copied())
+            .collect()
+    }
+
+    pub fn tile_type(&self, position: &Position) -> Option<TileType> {
+        self.position_idx(position)
+            .and_then(|idx| self.terrain[idx])
+            .and_then(|entity| self.entries.get(&entity))
+            .and_then(|entry| entry.tile_type)
+    }
+
+    /// Path finding cost of stepping onto `position`, see [`TileType::path_cost`].
+    fn path_cost(&self, x: i32, y: i32) -> f32 {
+        self.tile_type(&Position::new(x, y))
+            .map_or(1.0, |tile_type| tile_type.path_cost())
+    }
+
+    pub fn is_opaque_at(&self, position: &Position) -> bool {
+        self.position_idx(position)
+            .map_or(true, |idx| self.opaque[idx])
+    }
+
+    /// Changes whenever what can be seen through changes, so anything worked out from
+    /// line of sight can tell when it's out of date.
+    pub fn opacity_revision(&self) -> u64 {
+        self.opacity_revision
+    }
+
+    pub fn is_blocked(&self, position: &Position) -> bool {
+        self.position_idx(position)
+            .map_or(true, |idx| self.blocked[idx])
+    }
+
+    pub fn is_closed_door(&self, position: &Position) -> bool {
+        self.position_idx(position)
+            .map_or(false, |idx| self.closed_doors[idx])
+    }
+
+    /// Adds `entity` to the index, or moves it if it's there already.
+    pub fn index(&mut self, entity: Entity, entry: MapEntry) {
+        if self.entries.get(&entity) == Some(&entry) {
+            return;
+        }
+        self.unindex(entity);
+        let idx = match self.position_idx(&entry.position) {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        if entry.tile_type.is_some() {
+            self.terrain[idx] = Some(entity);
+        } else {
+            self.occupants
+                .entry(entry.position)
+                .or_insert_with(Vec::new)
+                .push(entity);
+        }
+        self.entries.insert(entity, entry);
+        self.refresh(idx, entry.position);
+    }
+
+    pub fn unindex(&mut self, entity: Entity) {
+        let entry = match self.entries.remove(&entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let idx = match self.position_idx(&entry.position) {
+            Some(idx) => idx,
+            None => return,
+        };
+
+        if entry.tile_type.is_some() {
+            if self.terrain[idx] == Some(entity) {
+                self.terrain[idx] = None;
+            }
+        } else if let Some(occupants) = self.occupants.get_mut(&entry.position) {
+            occupants.retain(|&e| e != entity);
+            if occupants.is_empty() {
+                self.occupants.remove(&entry.position);
+            }
+        }
+        self.refresh(idx, entry.position);
+    }
+
+    pub fn clear(&mut self) {
+        let opacity_revision = self.opacity_revision + 1;
+        *self = Self::new(self.width, self.height);
+        self.opacity_revision = opacity_revision;
+    }
+
+    /// Recomputes the flags of one cell from everything in it.
+    fn refresh(&mut self, idx: usize, position: Position) {
+        let (mut opaque, mut blocked, mut closed_door) = (false, false, false);
+        for entity in self.at_position(&position) {
+            if let Some(entry) = self.entries.get(&entity) {
+                opaque |= entry.opaque;
+                blocked |= entry.blocks;
+                closed_door |= entry.closed_door;
+            }
+        }
+        if self.opaque[idx] != opaque {
+            self.opacity_revision += 1;
+        }
+        self.opaque[idx] = opaque;
+        self.blocked[idx] = blocked;
+        self.closed_doors[idx] = closed_door;
     }
 
     pub fn idx_position<T>(&self, idx: T) -> Option<Position>
@@ -66,27 +239,121 @@ impl Map {
     }
 
     fn is_exit_valid(&self, x: i32, y: i32) -> bool {
-        if !self.in_bounds(Point::new(x, y)) {
-            return false;
+        let position = Position::new(x, y);
+        match self.position_idx(&position) {
+            Some(idx) => !self.blocked[idx] || self.closed_doors[idx],
+            None => false,
         }
-        !self.blockers.contains(&Position::new(x, y))
     }
 }
 
+/// Runs [`index_map`] once everything in [`CoreStage::PostUpdate`] has been applied,
+/// so entities despawned there, like the dead, are still seen going away.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct IndexMapStage;
+
+/// Parent of every tile entity of the current level.
+#[derive(Component)]
+pub struct MapRoot;
+
 pub struct MapPlugin;
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(build_map)
-            .add_system_to_stage(CoreStage::PreUpdate, collect_tiles);
+        // Empty until a run starts from the menu
+        app.insert_resource(Map::new(0, 0))
+            .init_resource::<Depth>()
+            .init_resource::<Dungeon>()
+            .add_event::<ChangeLevelEvent>()
+            .add_event::<NewRunEvent>()
+            .add_event::<MapReplacedEvent>()
+            // First thing in the frame, before anything holds on to the entities it replaces
+            .add_system_to_stage(CoreStage::First, new_run)
+            .add_system(handle_want_take_stairs)
+            .add_system(
+                change_level
+                    .after(handle_want_take_stairs)
+                    .label("change_level"),
+            )
+            .add_startup_system_to_stage(StartupStage::PostStartup, index_map)
+            .add_stage_after(
+                CoreStage::PostUpdate,
+                IndexMapStage,
+                SystemStage::single(index_map),
+            );
+    }
+}
+
+/// Despawns the current world, if any, and builds the first level of a new run with
+/// a fresh player in it.
+#[allow(clippy::too_many_arguments)]
+fn new_run(
+    mut commands: Commands,
+    mut events: EventReader<NewRunEvent>,
+    mut replaced_events: EventWriter<MapReplacedEvent>,
+    mut states: ResMut<State<GameState>>,
+    mut rng: ResMut<GameRng>,
+    mut depth: ResMut<Depth>,
+    mut dungeon: ResMut<Dungeon>,
+    mut memory: ResMut<Memory>,
+    mut game_log: ResMut<GameLog>,
+    mut stats: ResMut<RunStats>,
+    settings: Res<MapSettings>,
+    raws: Res<Raws>,
+    roots: Query<Entity, With<MapRoot>>,
+    level_entities: Query<Entity, (Or<(With<Position>, With<InBackpack>)>, Without<Parent>)>,
+) {
+    let event = match events.iter().last() {
+        Some(event) => event,
+        None => return,
+    };
+
+    for entity in roots.iter().chain(level_entities.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *rng = GameRng::seeded(event.seed);
+    depth.0 = 1;
+    *dungeon = Dungeon::default();
+    *memory = Memory::default();
+    *game_log = GameLog::new();
+    *stats = RunStats::default();
+
+    let player_start = generate_level(&mut commands, &mut rng, &settings, &raws, depth.0);
+    spawn_player(&mut commands, player_start, event.difficulty);
+    spawn_named(&mut commands, &raws, "Healing potion", player_start);
+    replaced_events.send(MapReplacedEvent);
+    game_log.push(format!(
+        "You enter the dungeon, {} difficulty",
+        event.difficulty.name().to_lowercase()
+    ));
+
+    if *states.current() != GameState::Gameplay {
+        states.set(GameState::Gameplay).unwrap();
     }
 }
 
-fn build_map(mut commands: Commands) {
-    let width = MAP_SIZE[0] as usize;
-    let height = MAP_SIZE[1] as usize;
-    let mut builder = RoomMapBuilder::new(width, height);
-    let map_info = builder.build();
+pub fn spawn_map_root(commands: &mut Commands, tile_entities: &[Entity]) -> Entity {
+    commands
+        .spawn()
+        .push_children(tile_entities)
+        .insert(MapRoot)
+        .insert(Name::new("Map"))
+        .id()
+}
+
+/// Builds a new level and spawns its tiles, monsters and items.
+/// Returns the position the player should be placed at.
+fn generate_level(
+    commands: &mut Commands,
+    rng: &mut RandomNumberGenerator,
+    settings: &MapSettings,
+    raws: &Raws,
+    depth: u32,
+) -> Position {
+    let width = settings.width;
+    let mut builder = settings.create_builder(depth);
+    let map_info = builder.build(rng);
 
     let map = Map::new(map_info.width, map_info.height);
     commands.insert_resource(map);
@@ -100,88 +367,229 @@ fn build_map(mut commands: Commands) {
             let y = i / width;
             (Position::new(x, y), tile)
         })
-        .map(|(position, tile)| tile.spawn(&mut commands, position))
+        .map(|(position, tile)| tile.spawn(commands, position))
         .collect::<Vec<_>>();
 
-    commands
-        .spawn()
-        .push_children(&tile_entities)
-        .insert(Name::new("Map"));
+    spawn_map_root(commands, &tile_entities);
 
-    spawn_player(
-        &mut commands,
-        map_info.player_start.map(|p| p.into()).unwrap_or_default(),
-    );
-
-    health_potion(&mut commands, map_info.player_start.unwrap().into());
+    for (point, entry) in map_info.roll_spawns(rng, &settings.spawn_table, depth) {
+        if spawn_named(commands, raws, &entry.name, point.into()).is_none() {
+            warn!(
+                "Spawn table refers to {:?}, which has no definition",
+                entry.name
+            );
+        }
+    }
 
-    map_info.rooms.iter().skip(1).for_each(|room| {
-        spawn_room(&mut commands, room);
-    });
+    map_info.player_start.map(|p| p.into()).unwrap_or_default()
 }
 
-fn spawn_room(commands: &mut Commands, room: &Rect) {
-    let mut spawned = HashMap::new();
-    let mut rng = RandomNumberGenerator::new();
-
-    let num_monsters = rng.roll_dice(1, 3) - 1;
-    assert!(num_monsters >= 0);
-    for _ in 0..num_monsters {
-        let mut added = false;
-        while !added {
-            let x = rng.range(room.x1 + 1, room.x2 - 1) as usize;
-            let y = rng.range(room.y1 + 1, room.y2 - 1) as usize;
-            if spawned.get(&(x, y)).is_none() {
-                spawned.insert((x, y), "monster");
-                added = true;
+fn handle_want_take_stairs(
+    mut commands: Commands,
+    mut game_log: ResMut<GameLog>,
+    mut change_level_events: EventWriter<ChangeLevelEvent>,
+    map: Res<Map>,
+    depth: Res<Depth>,
+    mut actors: Query<(Entity, &Position, &mut Energy, &WantTakeStairs)>,
+    stairs: Query<&Stairs>,
+) {
+    for (entity, position, mut energy, want) in actors.iter_mut() {
+        let on_stairs = map
+            .at_position(position)
+            .iter()
+            .any(|&e| stairs.get(e).ok() == Some(&want.0));
+        match (on_stairs, want.0) {
+            (true, Stairs::Down) => {
+                change_level_events.send(ChangeLevelEvent { depth: depth.0 + 1 });
+                energy.0 = 0;
             }
+            (true, Stairs::Up) => {
+                change_level_events.send(ChangeLevelEvent { depth: depth.0 - 1 });
+                energy.0 = 0;
+            }
+            (false, Stairs::Down) => game_log.push("There is no way down here"),
+            (false, Stairs::Up) => game_log.push("There is no way up here"),
         }
+        commands.entity(entity).remove::<WantTakeStairs>();
     }
+}
 
-    let num_items = rng.roll_dice(1, 2) - 1;
-    assert!(num_items >= 0);
-    for _ in 0..num_items {
-        let mut added = false;
-        while !added && spawned.len() < (room.width() * room.height()) as usize {
-            let x = rng.range(room.x1 + 1, room.x2 - 1) as usize;
-            let y = rng.range(room.y1 + 1, room.y2 - 1) as usize;
-            if spawned.get(&(x, y)).is_none() {
-                spawned.insert((x, y), "item");
-                added = true;
-            }
-        }
+/// Stores the current level away, keeping the player and everything in their backpack
+/// in the world, and drops the player into the next one. Levels visited before come
+/// back as they were left, new ones are built from scratch.
+#[allow(clippy::too_many_arguments)]
+fn change_level(
+    mut commands: Commands,
+    mut game_log: ResMut<GameLog>,
+    mut change_level_events: EventReader<ChangeLevelEvent>,
+    mut replaced_events: EventWriter<MapReplacedEvent>,
+    mut rng: ResMut<GameRng>,
+    settings: Res<MapSettings>,
+    raws: Res<Raws>,
+    map: Res<Map>,
+    mut depth: ResMut<Depth>,
+    mut dungeon: ResMut<Dungeon>,
+    mut memory: ResMut<Memory>,
+    roots: Query<Entity, With<MapRoot>>,
+    level_entities: Query<Entity, (With<Position>, Without<Player>, Without<Parent>)>,
+    player: Query<Entity, With<Player>>,
+    tiles: Query<(&Position, &TileType, Option<&Revealed>)>,
+    monsters: Query<
+        (
+            Entity,
+            &Position,
+            &Name,
+            &Tile,
+            &Health,
+            &Attack,
+            &Speed,
+            &Energy,
+            &Fov,
+            Option<&LightSource>,
+        ),
+        With<Monster>,
+    >,
+    items: Query<
+        (
+            Entity,
+            &Position,
+            &Name,
+            &Tile,
+            Option<&Potion>,
+            Option<&Fuel>,
+        ),
+        With<Item>,
+    >,
+) {
+    let event = match change_level_events.iter().last() {
+        Some(event) => event,
+        None => return,
+    };
+
+    let level = StoredLevel {
+        width: map.width(),
+        height: map.height(),
+        tiles: tiles
+            .iter()
+            .map(|(&position, &tile_type, revealed)| StoredTile {
+                position,
+                tile_type,
+                revealed: revealed.is_some(),
+            })
+            .collect(),
+        monsters: monsters
+            .iter()
+            .map(
+                |(entity, &position, name, &tile, &health, &attack, speed, energy, fov, light)| {
+                    StoredMonster {
+                        position,
+                        name: name.clone(),
+                        tile,
+                        health,
+                        attack,
+                        speed: speed.0,
+                        energy: energy.0,
+                        fov_range: fov.range,
+                        light: light.copied(),
+                        ghost: memory.take(entity),
+                    }
+                },
+            )
+            .collect(),
+        items: items
+            .iter()
+            .map(
+                |(entity, &position, name, &tile, potion, fuel)| StoredItem {
+                    position,
+                    name: name.clone(),
+                    tile,
+                    potion: potion.map(|potion| potion.heal_amount),
+                    fuel: fuel.map(|fuel| fuel.amount),
+                    ghost: memory.take(entity),
+                },
+            )
+            .collect(),
+        ghosts: memory.take_strays(),
+    };
+    dungeon.store(depth.0, level);
+
+    for entity in roots.iter().chain(level_entities.iter()) {
+        commands.entity(entity).despawn_recursive();
     }
 
-    for ((x, y), name) in spawned {
-        let position = Position::new(x, y);
-        match name {
-            "monster" => spawn_monster(commands, position),
-            "item" => health_potion(commands, position),
-            _ => unreachable!(),
-        };
+    let descending = event.depth > depth.0;
+    depth.0 = event.depth;
+    let player_start = match dungeon.take(depth.0) {
+        Some(level) => {
+            let arrival = if descending { Stairs::Up } else { Stairs::Down };
+            let player_start = level.stairs(arrival).unwrap_or_default();
+            level.restore(&mut commands, &mut memory);
+            player_start
+        }
+        None => generate_level(&mut commands, &mut rng, &settings, &raws, depth.0),
+    };
+
+    if let Ok(player) = player.get_single() {
+        // Inserted through commands so FOV is recomputed once the new map is collected
+        commands.entity(player).insert(player_start);
+    }
+    replaced_events.send(MapReplacedEvent);
+    if descending {
+        game_log.push(format!("You descend to depth {}", depth.0));
+    } else {
+        game_log.push(format!("You climb up to depth {}", depth.0));
     }
 }
 
-fn collect_tiles(
+/// Keeps [`Map`] in step with the world. Only entities that were added, moved, changed
+/// tile type or lost their position since the last run are touched, except after a
+/// [`MapReplacedEvent`], which gets everything indexed from scratch.
+pub fn index_map(
     mut map: ResMut<Map>,
-    tiles: Query<(Entity, &Position, Option<&Blocker>, Option<&Opaque>)>,
+    mut replaced_events: EventReader<MapReplacedEvent>,
+    all: Query<(
+        Entity,
+        &Position,
+        Option<&TileType>,
+        Option<&Blocker>,
+        Option<&Opaque>,
+    )>,
+    changed: Query<
+        (
+            Entity,
+            &Position,
+            Option<&TileType>,
+            Option<&Blocker>,
+            Option<&Opaque>,
+        ),
+        Or<(
+            Changed<Position>,
+            Changed<TileType>,
+            Added<Blocker>,
+            Added<Opaque>,
+        )>,
+    >,
+    removed_positions: RemovedComponents<Position>,
+    removed_blockers: RemovedComponents<Blocker>,
+    removed_opaque: RemovedComponents<Opaque>,
 ) {
-    map.tiles.clear();
-    map.opaque.clear();
-    map.blockers.clear();
-    for (entity, position, blocks_move, opaque) in tiles.iter() {
-        map.tiles
-            .entry(*position)
-            .or_insert(Vec::new())
-            .push(entity);
-
-        if opaque.is_some() {
-            map.opaque.insert(*position);
+    if replaced_events.iter().last().is_some() {
+        map.clear();
+        for (entity, &position, tile_type, blocker, opaque) in all.iter() {
+            map.index(entity, MapEntry::new(position, tile_type, blocker, opaque));
         }
+        return;
+    }
 
-        if blocks_move.is_some() {
-            map.blockers.insert(*position);
-        }
+    for entity in removed_positions.iter() {
+        map.unindex(entity);
+    }
+    let lost_flags = removed_blockers
+        .iter()
+        .chain(removed_opaque.iter())
+        .filter_map(|entity| all.get(entity).ok());
+    for (entity, &position, tile_type, blocker, opaque) in changed.iter().chain(lost_flags) {
+        map.index(entity, MapEntry::new(position, tile_type, blocker, opaque));
     }
 }
 
@@ -193,9 +601,7 @@ impl Algorithm2D for Map {
 
 impl BaseMap for Map {
     fn is_opaque(&self, idx: usize) -> bool {
-        self.idx_position(idx)
-            .map(|pos| self.opaque.contains(&pos))
-            .unwrap_or(true)
+        self.opaque.get(idx).copied().unwrap_or(true)
     }
 
     fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
@@ -206,30 +612,30 @@ impl BaseMap for Map {
 
         // Cardinal directions
         if self.is_exit_valid(x - 1, y) {
-            exits.push((idx - 1, 1.0))
+            exits.push((idx - 1, self.path_cost(x - 1, y)))
         };
         if self.is_exit_valid(x + 1, y) {
-            exits.push((idx + 1, 1.0))
+            exits.push((idx + 1, self.path_cost(x + 1, y)))
         };
         if self.is_exit_valid(x, y - 1) {
-            exits.push((idx - w, 1.0))
+            exits.push((idx - w, self.path_cost(x, y - 1)))
         };
         if self.is_exit_valid(x, y + 1) {
-            exits.push((idx + w, 1.0))
+            exits.push((idx + w, self.path_cost(x, y + 1)))
         };
 
         // Diagonals
         if self.is_exit_valid(x - 1, y - 1) {
-            exits.push(((idx - w) - 1, 1.45));
+            exits.push(((idx - w) - 1, 1.45 * self.path_cost(x - 1, y - 1)));
         }
         if self.is_exit_valid(x + 1, y - 1) {
-            exits.push(((idx - w) + 1, 1.45));
+            exits.push(((idx - w) + 1, 1.45 * self.path_cost(x + 1, y - 1)));
         }
         if self.is_exit_valid(x - 1, y + 1) {
-            exits.push(((idx + w) - 1, 1.45));
+            exits.push(((idx + w) - 1, 1.45 * self.path_cost(x - 1, y + 1)));
         }
         if self.is_exit_valid(x + 1, y + 1) {
-            exits.push(((idx + w) + 1, 1.45));
+            exits.push(((idx + w) + 1, 1.45 * self.path_cost(x + 1, y + 1)));
         }
 
         exits
@@ -243,155 +649,45 @@ impl BaseMap for Map {
     }
 }
 
-pub struct MapInfo {
-    pub width: usize,
-    pub height: usize,
-    pub tiles: Vec<TileType>,
-    pub rooms: Vec<Rect>,
-    pub player_start: Option<Point>,
-}
-
-impl MapInfo {
-    pub fn new(width: usize, height: usize) -> Self {
-        Self {
-            width,
-            height,
-            player_start: None,
-            rooms: vec![],
-            tiles: vec![TileType::Wall; width * height],
-        }
-    }
-
-    pub fn xy_idx<T: TryInto<usize>>(&self, x: T, y: T) -> Option<usize> {
-        let x = x.try_into().ok()?;
-        let y = y.try_into().ok()?;
-        if x > self.width || y > self.height {
-            None
-        } else {
-            let idx = y * self.width + x;
-            Some(idx)
-        }
-    }
-}
-
-pub trait MapBuilder {
-    fn build(&mut self) -> MapInfo;
-}
-
-pub struct RoomMapBuilder {
-    width: usize,
-    height: usize,
-    rooms: Vec<Rect>,
-    min_room_size: usize,
-    max_room_size: usize,
-    rng: RandomNumberGenerator,
-}
-
-impl RoomMapBuilder {
-    pub fn new(width: usize, height: usize) -> Self {
-        let min_room_size = 4;
-        let max_room_size = (width / 3).min(height / 3).max(min_room_size);
-        assert!(min_room_size <= max_room_size);
-        Self {
-            width,
-            height,
-            min_room_size,
-            max_room_size,
-            rng: RandomNumberGenerator::new(),
-            rooms: Vec::new(),
-        }
-    }
-}
-
-impl MapBuilder for RoomMapBuilder {
-    fn build(&mut self) -> MapInfo {
-        const MAX_ROOMS: usize = 30;
-        let mut map = MapInfo::new(self.width, self.height);
-        for _ in 0..MAX_ROOMS {
-            let room = self.build_random_room();
-            if self.rooms.iter().any(|r| r.intersect(&room)) {
-                continue;
-            }
-
-            self.apply_room(&mut map, &room);
-            if !self.rooms.is_empty() {
-                let Point { x: new_x, y: new_y } = room.center();
-                let Point {
-                    x: prev_x,
-                    y: prev_y,
-                } = self.rooms[self.rooms.len() - 1].center();
-
-                if self.rng.rand::<bool>() {
-                    apply_horizontal_tunnel(&mut map, prev_x, new_x, prev_y);
-                    apply_vertical_tunnel(&mut map, prev_y, new_y, new_x);
-                } else {
-                    apply_vertical_tunnel(&mut map, prev_y, new_y, prev_x);
-                    apply_horizontal_tunnel(&mut map, prev_x, new_x, new_y);
-                }
-            }
-            self.rooms.push(room);
-        }
-
-        let player_pos = self
-            .rooms
-            .get(0)
-            .map(|r| r.center())
-            .unwrap_or_else(Point::zero);
-
-        map.player_start = Some(player_pos);
-        map.rooms = self.rooms.clone();
-        map
-    }
-}
-
-impl RoomMapBuilder {
-    fn build_random_room(&mut self) -> Rect {
-        let w = self.rng.range(self.min_room_size, self.max_room_size + 1);
-        let h = self.rng.range(self.min_room_size, self.max_room_size + 1);
-        let x = self.rng.range(2, self.width - w - 1) - 1;
-        let y = self.rng.range(2, self.height - h - 1) - 1;
-
-        Rect::with_size(x, y, w, h)
-    }
-
-    fn apply_room(&self, map: &mut MapInfo, room: &Rect) {
-        let mut rng = RandomNumberGenerator::new();
-        let mut noise = FastNoise::seeded(rng.next_u64());
-        noise.set_noise_type(NoiseType::PerlinFractal);
-        noise.set_fractal_type(FractalType::FBM);
-        noise.set_fractal_octaves(5);
-        noise.set_fractal_gain(0.6);
-        noise.set_fractal_lacunarity(2.0);
-        noise.set_frequency(8.0);
-
-        for x in (room.x1)..room.x2 {
-            for y in (room.y1)..room.y2 {
-                let index = y as usize * self.width + x as usize;
-                let n =
-                    noise.get_noise(x as f32 / self.width as f32, y as f32 / self.height as f32);
-                let is_grass = n < 0.0;
-                if is_grass {
-                    map.tiles[index] = TileType::Grass;
-                } else {
-                    map.tiles[index] = TileType::Floor;
-                }
-            }
-        }
-    }
-}
-
-fn apply_horizontal_tunnel(map: &mut MapInfo, x1: i32, x2: i32, y: i32) {
-    for x in x1.min(x2)..=x1.max(x2) {
-        if let Some(idx) = map.xy_idx(x, y) {
-            map.tiles[idx] = TileType::Floor;
-        }
-    }
-}
-
-fn apply_vertical_tunnel(map: &mut MapInfo, y1: i32, y2: i32, x: i32) {
-    for y in y1.min(y2)..=y1.max(y2) {
-        if let Some(idx) = map.xy_idx(x, y) {
-            map.tiles[idx] = TileType::Floor;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::track_dead;
+
+    #[test]
+    fn dead_monsters_free_their_tile() {
+        let mut app = App::new();
+        app.add_state::<GameState>(GameState::Gameplay)
+            .add_event::<MapReplacedEvent>()
+            .init_resource::<GameLog>()
+            .insert_resource(Map::new(3, 1))
+            .add_system_to_stage(CoreStage::PostUpdate, track_dead)
+            .add_stage_after(
+                CoreStage::PostUpdate,
+                IndexMapStage,
+                SystemStage::single(index_map),
+            );
+
+        let position = Position::new(1, 0);
+        let monster = app
+            .world
+            .spawn()
+            .insert(position)
+            .insert(Blocker)
+            .insert(Health::new(1))
+            .id();
+        app.update();
+        assert!(app
+            .world
+            .get_resource::<Map>()
+            .unwrap()
+            .is_blocked(&position));
+
+        app.world.get_mut::<Health>(monster).unwrap().take_damage(1);
+        app.update();
+        assert!(app.world.get_entity(monster).is_none());
+        let map = app.world.get_resource::<Map>().unwrap();
+        assert!(!map.is_blocked(&position));
+        assert!(map.at_position(&position).is_empty());
     }
 }
@@ -0,0 +1,112 @@
+use bracket_lib::prelude::{RandomNumberGenerator, Rect};
+
+use super::{apply_room, apply_tunnel, MapBuilder, MapInfo};
+
+/// Splits the map area recursively, puts a room in every leaf
+/// and joins sibling nodes with corridors.
+pub struct BspMapBuilder {
+    width: usize,
+    height: usize,
+    min_leaf_size: i32,
+    min_room_size: i32,
+}
+
+impl BspMapBuilder {
+    pub fn new(width: usize, height: usize) -> Self {
+        let min_room_size = 4;
+        let min_leaf_size = min_room_size + 4;
+        Self {
+            width,
+            height,
+            min_leaf_size,
+            min_room_size,
+        }
+    }
+}
+
+impl MapBuilder for BspMapBuilder {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapInfo {
+        let mut map = MapInfo::new(self.width, self.height);
+        let area = Rect::with_size(1, 1, self.width as i32 - 2, self.height as i32 - 2);
+        let rooms = self.split(&mut map, rng, area);
+
+        map.player_start = rooms.first().map(|r| r.center());
+        map.rooms = rooms;
+        map
+    }
+}
+
+impl BspMapBuilder {
+    /// Returns the rooms placed inside `area`, already connected to each other.
+    fn split(&self, map: &mut MapInfo, rng: &mut RandomNumberGenerator, area: Rect) -> Vec<Rect> {
+        let width = area.width();
+        let height = area.height();
+        let can_split_x = width >= self.min_leaf_size * 2;
+        let can_split_y = height >= self.min_leaf_size * 2;
+
+        let split_x = match (can_split_x, can_split_y) {
+            (false, false) => return self.place_room(map, rng, area).into_iter().collect(),
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => {
+                // Prefer cutting across the longer side to keep leaves close to square
+                let ratio = width as f32 / height as f32;
+                if ratio >= 1.25 {
+                    true
+                } else if ratio <= 0.8 {
+                    false
+                } else {
+                    rng.rand::<bool>()
+                }
+            }
+        };
+
+        let (first, second) = if split_x {
+            let cut = rng.range(self.min_leaf_size, width - self.min_leaf_size + 1);
+            (
+                Rect::with_size(area.x1, area.y1, cut, height),
+                Rect::with_size(area.x1 + cut, area.y1, width - cut, height),
+            )
+        } else {
+            let cut = rng.range(self.min_leaf_size, height - self.min_leaf_size + 1);
+            (
+                Rect::with_size(area.x1, area.y1, width, cut),
+                Rect::with_size(area.x1, area.y1 + cut, width, height - cut),
+            )
+        };
+
+        let first = self.split(map, rng, first);
+        let second = self.split(map, rng, second);
+
+        let from = rng.random_slice_entry(&first).map(|r| r.center());
+        let to = rng.random_slice_entry(&second).map(|r| r.center());
+        if let Some((from, to)) = from.zip(to) {
+            apply_tunnel(map, rng, from, to);
+        }
+
+        first.into_iter().chain(second).collect()
+    }
+
+    fn place_room(
+        &self,
+        map: &mut MapInfo,
+        rng: &mut RandomNumberGenerator,
+        leaf: Rect,
+    ) -> Option<Rect> {
+        // Leave at least one tile of wall between the room and the leaf border
+        let max_width = leaf.width() - 2;
+        let max_height = leaf.height() - 2;
+        if max_width < self.min_room_size || max_height < self.min_room_size {
+            return None;
+        }
+
+        let w = rng.range(self.min_room_size, max_width + 1);
+        let h = rng.range(self.min_room_size, max_height + 1);
+        let x = leaf.x1 + rng.range(1, leaf.width() - w);
+        let y = leaf.y1 + rng.range(1, leaf.height() - h);
+        let room = Rect::with_size(x, y, w, h);
+
+        apply_room(map, &room);
+        Some(room)
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+
+use super::{MapBuilder, MapInfo};
+use crate::map_tile::TileType;
+
+/// Grows organic caves from random noise with a few smoothing passes.
+pub struct CellularAutomataMapBuilder {
+    width: usize,
+    height: usize,
+    floor_chance: i32,
+    iterations: usize,
+    region_size: usize,
+}
+
+impl CellularAutomataMapBuilder {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            floor_chance: 55,
+            iterations: 12,
+            region_size: 8,
+        }
+    }
+}
+
+impl MapBuilder for CellularAutomataMapBuilder {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapInfo {
+        let mut map = MapInfo::new(self.width, self.height);
+
+        for y in 1..self.height - 1 {
+            for x in 1..self.width - 1 {
+                let idx = y * self.width + x;
+                if rng.roll_dice(1, 100) <= self.floor_chance {
+                    map.tiles[idx] = TileType::Floor;
+                }
+            }
+        }
+
+        for _ in 0..self.iterations {
+            self.smooth(&mut map);
+        }
+
+        self.keep_largest_cave(&mut map);
+        map.player_start = map.closest_floor(Point::new(self.width / 2, self.height / 2));
+        map.regions = map.sample_regions(self.region_size);
+        map
+    }
+}
+
+impl CellularAutomataMapBuilder {
+    fn smooth(&self, map: &mut MapInfo) {
+        let mut tiles = map.tiles.clone();
+        for y in 1..self.height - 1 {
+            for x in 1..self.width - 1 {
+                let walls = self
+                    .neighbours(x, y)
+                    .filter(|&idx| map.tiles[idx] == TileType::Wall)
+                    .count();
+                tiles[y * self.width + x] = if walls > 4 || walls == 0 {
+                    TileType::Wall
+                } else {
+                    TileType::Floor
+                };
+            }
+        }
+        map.tiles = tiles;
+    }
+
+    fn neighbours(&self, x: usize, y: usize) -> impl Iterator<Item = usize> + '_ {
+        (y - 1..=y + 1)
+            .flat_map(move |ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+            .filter(move |&(nx, ny)| (nx, ny) != (x, y))
+            .map(move |(nx, ny)| ny * self.width + nx)
+    }
+
+    /// Walls off every cave except the biggest one so the whole map is reachable.
+    fn keep_largest_cave(&self, map: &mut MapInfo) {
+        let mut cave_of = vec![None; map.tiles.len()];
+        let mut cave_sizes = Vec::new();
+
+        for start in 0..map.tiles.len() {
+            if map.tiles[start].is_blocking() || cave_of[start].is_some() {
+                continue;
+            }
+
+            let cave = cave_sizes.len();
+            let mut size = 0;
+            let mut queue = VecDeque::from([start]);
+            cave_of[start] = Some(cave);
+            while let Some(idx) = queue.pop_front() {
+                size += 1;
+                // Borders are always walls, so neighbours of a floor tile stay in bounds
+                for next in [idx - 1, idx + 1, idx - self.width, idx + self.width] {
+                    if !map.tiles[next].is_blocking() && cave_of[next].is_none() {
+                        cave_of[next] = Some(cave);
+                        queue.push_back(next);
+                    }
+                }
+            }
+            cave_sizes.push(size);
+        }
+
+        let largest = cave_sizes
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, &size)| size)
+            .map(|(cave, _)| cave);
+
+        for (tile, cave) in map.tiles.iter_mut().zip(cave_of) {
+            if cave.is_some() && cave != largest {
+                *tile = TileType::Wall;
+            }
+        }
+    }
+}
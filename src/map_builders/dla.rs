@@ -0,0 +1,65 @@
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+
+use super::{dig, random_step, MapBuilder, MapInfo, WalkerSettings};
+
+/// Diffusion-limited aggregation: particles wander in from random spots
+/// and stick to the dug area as soon as they bump into it.
+pub struct DlaMapBuilder {
+    width: usize,
+    height: usize,
+    pub settings: WalkerSettings,
+    region_size: usize,
+}
+
+impl DlaMapBuilder {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            settings: WalkerSettings {
+                walkers: 5000,
+                lifetime: 1000,
+                floor_percent: 30,
+            },
+            region_size: 8,
+        }
+    }
+}
+
+impl MapBuilder for DlaMapBuilder {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapInfo {
+        let mut map = MapInfo::new(self.width, self.height);
+        let start = Point::new(self.width / 2, self.height / 2);
+        let target = map.tiles.len() * self.settings.floor_percent / 100;
+
+        // A small cross seeds the aggregate so the first particles have something to hit
+        for (dx, dy) in [(0, 0), (-1, 0), (1, 0), (0, -1), (0, 1)] {
+            dig(&mut map, start.x + dx, start.y + dy);
+        }
+
+        for _ in 0..self.settings.walkers {
+            if map.floor_count() >= target {
+                break;
+            }
+
+            let mut position = Point::new(
+                rng.range(1, self.width as i32 - 1),
+                rng.range(1, self.height as i32 - 1),
+            );
+            let mut previous = position;
+            for _ in 0..self.settings.lifetime {
+                let idx = map.xy_idx(position.x, position.y).unwrap();
+                if !map.tiles[idx].is_blocking() {
+                    dig(&mut map, previous.x, previous.y);
+                    break;
+                }
+                previous = position;
+                position = random_step(&map, rng, position);
+            }
+        }
+
+        map.player_start = Some(start);
+        map.regions = map.sample_regions(self.region_size);
+        map
+    }
+}
@@ -0,0 +1,52 @@
+use bracket_lib::prelude::{Point, RandomNumberGenerator};
+
+use super::{dig, random_step, MapBuilder, MapInfo, WalkerSettings};
+
+/// Digs the map with walkers that stumble around at random. The first one
+/// starts in the centre, every following one from an already dug tile.
+pub struct DrunkardsWalkMapBuilder {
+    width: usize,
+    height: usize,
+    pub settings: WalkerSettings,
+    region_size: usize,
+}
+
+impl DrunkardsWalkMapBuilder {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            settings: WalkerSettings {
+                walkers: 200,
+                lifetime: 400,
+                floor_percent: 50,
+            },
+            region_size: 8,
+        }
+    }
+}
+
+impl MapBuilder for DrunkardsWalkMapBuilder {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapInfo {
+        let mut map = MapInfo::new(self.width, self.height);
+        let start = Point::new(self.width / 2, self.height / 2);
+        let target = map.tiles.len() * self.settings.floor_percent / 100;
+
+        dig(&mut map, start.x, start.y);
+        for _ in 0..self.settings.walkers {
+            if map.floor_count() >= target {
+                break;
+            }
+
+            let mut position = *rng.random_slice_entry(&map.floor_points()).unwrap();
+            for _ in 0..self.settings.lifetime {
+                dig(&mut map, position.x, position.y);
+                position = random_step(&map, rng, position);
+            }
+        }
+
+        map.player_start = Some(start);
+        map.regions = map.sample_regions(self.region_size);
+        map
+    }
+}
@@ -0,0 +1,442 @@
+mod bsp;
+mod cellular;
+mod dla;
+mod drunkard;
+mod modifiers;
+mod prefab;
+mod rooms;
+
+use std::{cmp::Ordering, collections::BTreeMap, str::FromStr};
+
+use bracket_lib::prelude::{
+    Algorithm2D, BaseMap, DijkstraMap, Point, RandomNumberGenerator, Rect, SmallVec,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::Position,
+    map_tile::TileType,
+    spawn_table::{SpawnEntry, SpawnTable},
+};
+
+pub use bsp::BspMapBuilder;
+pub use cellular::CellularAutomataMapBuilder;
+pub use dla::DlaMapBuilder;
+pub use drunkard::DrunkardsWalkMapBuilder;
+pub use modifiers::{
+    ConnectivityCheck, DoorPlacement, GrassScatter, MapTransform, StairsPlacement, TerrainFeatures,
+    TorchPlacement, Unreachable,
+};
+pub use prefab::{Prefab, PrefabStamp};
+pub use rooms::RoomMapBuilder;
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (-1, 0),
+    (1, 0),
+    (0, -1),
+    (0, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+    (1, 1),
+];
+
+pub struct MapInfo {
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<TileType>,
+    pub rooms: Vec<Rect>,
+    /// Spawn areas for maps without rooms, such as caves
+    pub regions: Vec<Vec<Point>>,
+    /// Hand-authored areas stamped from prefabs
+    pub vaults: Vec<Rect>,
+    /// Fixed spawns placed by prefabs, on top of the random ones
+    pub spawns: Vec<(Point, Spawn)>,
+    pub player_start: Option<Point>,
+}
+
+/// Whether a spawn is a monster or an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Spawn {
+    Monster,
+    Item,
+}
+
+impl MapInfo {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            player_start: None,
+            rooms: vec![],
+            regions: vec![],
+            vaults: vec![],
+            spawns: vec![],
+            tiles: vec![TileType::Wall; width * height],
+        }
+    }
+
+    pub fn xy_idx<T: TryInto<usize>>(&self, x: T, y: T) -> Option<usize> {
+        let x = x.try_into().ok()?;
+        let y = y.try_into().ok()?;
+        if x >= self.width || y >= self.height {
+            None
+        } else {
+            let idx = y * self.width + x;
+            Some(idx)
+        }
+    }
+
+    /// Candidate spawn points grouped by area: the inside of every room,
+    /// or the sampled regions when the builder produced no rooms.
+    pub fn spawn_regions(&self) -> Vec<Vec<Point>> {
+        let regions = if self.rooms.is_empty() {
+            self.regions.clone()
+        } else {
+            self.rooms
+                .iter()
+                .map(|room| {
+                    (room.y1 + 1..room.y2 - 1)
+                        .flat_map(|y| (room.x1 + 1..room.x2 - 1).map(move |x| Point::new(x, y)))
+                        .collect()
+                })
+                .collect()
+        };
+
+        // Later passes may have walled some of it off, or flooded it
+        regions
+            .into_iter()
+            .map(|region: Vec<Point>| {
+                region
+                    .into_iter()
+                    .filter(|p| {
+                        self.xy_idx(p.x, p.y)
+                            .and_then(|idx| self.tiles.get(idx))
+                            .map_or(false, |tile| !tile.is_blocking() && !tile.is_hazard())
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|region| !region.is_empty())
+            .collect()
+    }
+
+    /// Chops the map into square chunks and returns the floor tiles of every
+    /// chunk that has enough of them to be worth spawning in.
+    pub fn sample_regions(&self, region_size: usize) -> Vec<Vec<Point>> {
+        let min_region_tiles = region_size * region_size / 4;
+        let mut regions = Vec::new();
+        for chunk_y in (0..self.height).step_by(region_size) {
+            for chunk_x in (0..self.width).step_by(region_size) {
+                let region = (chunk_y..(chunk_y + region_size).min(self.height))
+                    .flat_map(|y| {
+                        (chunk_x..(chunk_x + region_size).min(self.width)).map(move |x| (x, y))
+                    })
+                    .filter(|&(x, y)| !self.tiles[y * self.width + x].is_blocking())
+                    .map(|(x, y)| Point::new(x, y))
+                    .collect::<Vec<_>>();
+                if region.len() >= min_region_tiles {
+                    regions.push(region);
+                }
+            }
+        }
+        regions
+    }
+
+    /// Dijkstra flood from `start`: walking cost to every tile, `None` where it can't be reached.
+    pub fn distances_from(&self, start: Point) -> Vec<Option<f32>> {
+        let start = match self.xy_idx(start.x, start.y) {
+            Some(idx) => idx,
+            None => return vec![None; self.tiles.len()],
+        };
+        let max_depth = (self.width * self.height) as f32;
+        let dijkstra = DijkstraMap::new(self.width, self.height, &[start], self, max_depth);
+        dijkstra
+            .map
+            .iter()
+            .map(|&distance| (distance < f32::MAX).then(|| distance))
+            .collect()
+    }
+
+    /// The reachable floor tile furthest away from `start`.
+    pub fn farthest_floor(&self, start: Point) -> Option<Point> {
+        self.distances_from(start)
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, distance)| distance.map(|distance| (idx, distance)))
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+            .map(|(idx, _)| Point::new(idx % self.width, idx / self.width))
+    }
+
+    /// Every spawn on the level: the markers from prefabs, then a few random picks from
+    /// `table` in each spawn region except the one the player starts in.
+    pub fn roll_spawns(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        table: &SpawnTable,
+        depth: u32,
+    ) -> Vec<(Point, SpawnEntry)> {
+        let table = table.for_depth(depth);
+        let mut spawns = Vec::new();
+        for &(point, kind) in self.spawns.iter() {
+            if let Some(entry) = table.of_kind(kind).roll(rng) {
+                spawns.push((point, entry.clone()));
+            }
+        }
+
+        let player_start = self.player_start;
+        self.spawn_regions()
+            .iter()
+            .filter(|region| !player_start.map_or(false, |start| region.contains(&start)))
+            .for_each(|region| spawns.extend(roll_region_spawns(rng, &table, depth, region)));
+        spawns
+    }
+
+    pub fn floor_count(&self) -> usize {
+        self.tiles.iter().filter(|tile| !tile.is_blocking()).count()
+    }
+
+    pub fn floor_points(&self) -> Vec<Point> {
+        self.tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, tile)| !tile.is_blocking())
+            .map(|(idx, _)| Point::new(idx % self.width, idx / self.width))
+            .collect()
+    }
+
+    /// The floor tile closest to `target`.
+    pub fn closest_floor(&self, target: Point) -> Option<Point> {
+        let target = Position::from(&target);
+        self.floor_points()
+            .into_iter()
+            .min_by_key(|point| target.distance_squared(&point.into()))
+    }
+}
+
+impl Algorithm2D for MapInfo {
+    fn dimensions(&self) -> Point {
+        Point::new(self.width, self.height)
+    }
+}
+
+impl BaseMap for MapInfo {
+    fn is_opaque(&self, idx: usize) -> bool {
+        self.tiles.get(idx).map_or(true, |tile| tile.is_opaque())
+    }
+
+    fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
+        let x = (idx % self.width) as i32;
+        let y = (idx / self.width) as i32;
+        NEIGHBOURS
+            .iter()
+            .filter_map(|&(dx, dy)| {
+                let next = self.xy_idx(x + dx, y + dy)?;
+                // Lava and deep water don't count as a way through while building
+                if !self.tiles[next].is_passable() || self.tiles[next].is_hazard() {
+                    return None;
+                }
+                let cost = if dx != 0 && dy != 0 { 1.45 } else { 1.0 };
+                Some((next, cost))
+            })
+            .collect()
+    }
+}
+
+pub trait MapBuilder {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapInfo;
+}
+
+/// A pass that reworks a map some builder has already produced.
+pub trait MapModifier {
+    fn modify(&mut self, map: &mut MapInfo, rng: &mut RandomNumberGenerator);
+}
+
+/// One initial builder followed by any number of modifier passes, run in order.
+pub struct BuilderChain {
+    initial: Box<dyn MapBuilder>,
+    modifiers: Vec<Box<dyn MapModifier>>,
+}
+
+impl BuilderChain {
+    pub fn new(initial: Box<dyn MapBuilder>) -> Self {
+        Self {
+            initial,
+            modifiers: Vec::new(),
+        }
+    }
+
+    pub fn with<M: MapModifier + 'static>(mut self, modifier: M) -> Self {
+        self.modifiers.push(Box::new(modifier));
+        self
+    }
+}
+
+impl MapBuilder for BuilderChain {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapInfo {
+        let mut map = self.initial.build(rng);
+        for modifier in self.modifiers.iter_mut() {
+            modifier.modify(&mut map, rng);
+        }
+        map
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapBuilderKind {
+    Rooms,
+    Bsp,
+    Caves,
+    Drunkard,
+    Dla,
+}
+
+impl MapBuilderKind {
+    pub const ALL: [MapBuilderKind; 5] = [
+        MapBuilderKind::Rooms,
+        MapBuilderKind::Bsp,
+        MapBuilderKind::Caves,
+        MapBuilderKind::Drunkard,
+        MapBuilderKind::Dla,
+    ];
+
+    /// The name it goes by on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MapBuilderKind::Rooms => "rooms",
+            MapBuilderKind::Bsp => "bsp",
+            MapBuilderKind::Caves => "caves",
+            MapBuilderKind::Drunkard => "drunkard",
+            MapBuilderKind::Dla => "dla",
+        }
+    }
+
+    pub fn create(&self, width: usize, height: usize) -> Box<dyn MapBuilder> {
+        match self {
+            MapBuilderKind::Rooms => Box::new(RoomMapBuilder::new(width, height)),
+            MapBuilderKind::Bsp => Box::new(BspMapBuilder::new(width, height)),
+            MapBuilderKind::Caves => Box::new(CellularAutomataMapBuilder::new(width, height)),
+            MapBuilderKind::Drunkard => Box::new(DrunkardsWalkMapBuilder::new(width, height)),
+            MapBuilderKind::Dla => Box::new(DlaMapBuilder::new(width, height)),
+        }
+    }
+}
+
+impl Default for MapBuilderKind {
+    fn default() -> Self {
+        MapBuilderKind::Rooms
+    }
+}
+
+impl FromStr for MapBuilderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rooms" => Ok(MapBuilderKind::Rooms),
+            "bsp" => Ok(MapBuilderKind::Bsp),
+            "caves" => Ok(MapBuilderKind::Caves),
+            "drunkard" => Ok(MapBuilderKind::Drunkard),
+            "dla" => Ok(MapBuilderKind::Dla),
+            _ => Err(format!("Unknown map builder: {}", s)),
+        }
+    }
+}
+
+fn dig(map: &mut MapInfo, x: i32, y: i32) {
+    let index = y as usize * map.width + x as usize;
+    map.tiles[index] = TileType::Floor;
+}
+
+fn apply_room(map: &mut MapInfo, room: &Rect) {
+    for x in (room.x1)..room.x2 {
+        for y in (room.y1)..room.y2 {
+            dig(map, x, y);
+        }
+    }
+}
+
+fn roll_region_spawns(
+    rng: &mut RandomNumberGenerator,
+    table: &SpawnTable,
+    depth: u32,
+    region: &[Point],
+) -> Vec<(Point, SpawnEntry)> {
+    const MAX_SPAWNS: i32 = 4;
+    // Ordered so that spawning (and the rolls it makes) doesn't depend on hashing
+    let mut spawned = BTreeMap::new();
+
+    let num_spawns = (rng.roll_dice(1, MAX_SPAWNS) + depth as i32 / 2 - 1).max(0);
+    for _ in 0..num_spawns {
+        if spawned.len() >= region.len() {
+            break;
+        }
+        let entry = match table.roll(rng) {
+            Some(entry) => entry,
+            None => break,
+        };
+        loop {
+            let point = rng.random_slice_entry(region).unwrap();
+            if spawned.get(&(point.x, point.y)).is_none() {
+                spawned.insert((point.x, point.y), entry.clone());
+                break;
+            }
+        }
+    }
+
+    spawned
+        .into_iter()
+        .map(|((x, y), entry)| (Point::new(x, y), entry))
+        .collect()
+}
+
+/// Knobs shared by the random-walk builders.
+#[derive(Debug, Clone, Copy)]
+pub struct WalkerSettings {
+    /// Upper bound on how many walkers are released
+    pub walkers: usize,
+    /// How many steps a single walker takes before it gives up
+    pub lifetime: usize,
+    /// Generation stops once this share of the map is floor
+    pub floor_percent: usize,
+}
+
+/// Moves one step in a random cardinal direction, staying off the map border.
+fn random_step(map: &MapInfo, rng: &mut RandomNumberGenerator, point: Point) -> Point {
+    let (dx, dy) = match rng.roll_dice(1, 4) {
+        1 => (-1, 0),
+        2 => (1, 0),
+        3 => (0, -1),
+        _ => (0, 1),
+    };
+    Point::new(
+        (point.x + dx).clamp(1, map.width as i32 - 2),
+        (point.y + dy).clamp(1, map.height as i32 - 2),
+    )
+}
+
+/// Digs an L-shaped corridor between two points, randomly picking which leg goes first.
+fn apply_tunnel(map: &mut MapInfo, rng: &mut RandomNumberGenerator, from: Point, to: Point) {
+    if rng.rand::<bool>() {
+        apply_horizontal_tunnel(map, from.x, to.x, from.y);
+        apply_vertical_tunnel(map, from.y, to.y, to.x);
+    } else {
+        apply_vertical_tunnel(map, from.y, to.y, from.x);
+        apply_horizontal_tunnel(map, from.x, to.x, to.y);
+    }
+}
+
+fn apply_horizontal_tunnel(map: &mut MapInfo, x1: i32, x2: i32, y: i32) {
+    for x in x1.min(x2)..=x1.max(x2) {
+        if let Some(idx) = map.xy_idx(x, y) {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+fn apply_vertical_tunnel(map: &mut MapInfo, y1: i32, y2: i32, x: i32) {
+    for y in y1.min(y2)..=y1.max(y2) {
+        if let Some(idx) = map.xy_idx(x, y) {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
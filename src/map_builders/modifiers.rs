@@ -0,0 +1,390 @@
+use std::str::FromStr;
+
+use bracket_lib::prelude::{FastNoise, FractalType, NoiseType, Point, RandomNumberGenerator, Rect};
+use serde::{Deserialize, Serialize};
+
+use super::{apply_tunnel, MapInfo, MapModifier};
+use crate::{components::Position, map_tile::TileType};
+
+/// What [`ConnectivityCheck`] does with floor that can't be reached from the start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unreachable {
+    /// Dig a corridor from every isolated area to the nearest reachable floor
+    Connect,
+    /// Turn isolated areas back into walls
+    Cull,
+}
+
+/// Floods the map from the player start and makes sure every floor tile is reachable
+/// without crossing lava or deep water, reporting whenever it had to change something.
+pub struct ConnectivityCheck {
+    pub unreachable: Unreachable,
+}
+
+impl MapModifier for ConnectivityCheck {
+    fn modify(&mut self, map: &mut MapInfo, rng: &mut RandomNumberGenerator) {
+        let start = match map.player_start {
+            Some(start) => start,
+            None => {
+                eprintln!("Connectivity: map has no player start, skipping the check");
+                return;
+            }
+        };
+        if map
+            .xy_idx(start.x, start.y)
+            .map_or(true, |idx| map.tiles[idx].is_blocking())
+        {
+            eprintln!(
+                "Connectivity: player start ({}, {}) is not on floor, digging it out",
+                start.x, start.y
+            );
+            if let Some(idx) = map.xy_idx(start.x, start.y) {
+                map.tiles[idx] = TileType::Floor;
+            }
+        }
+
+        match self.unreachable {
+            Unreachable::Connect => connect_isolated(map, rng, start),
+            Unreachable::Cull => cull_isolated(map, start),
+        }
+    }
+}
+
+fn unreachable_floor(map: &MapInfo, start: Point) -> Vec<usize> {
+    map.distances_from(start)
+        .iter()
+        .enumerate()
+        .filter(|&(idx, distance)| {
+            let tile = map.tiles[idx];
+            distance.is_none() && tile.is_passable() && !tile.is_hazard()
+        })
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+fn connect_isolated(map: &mut MapInfo, rng: &mut RandomNumberGenerator, start: Point) {
+    let mut corridors = 0;
+    loop {
+        let isolated = unreachable_floor(map, start);
+        let from = match isolated.first() {
+            Some(&idx) => Point::new(idx % map.width, idx / map.width),
+            None => break,
+        };
+
+        let reachable = map
+            .distances_from(start)
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, distance)| distance.map(|_| idx))
+            .map(|idx| Point::new(idx % map.width, idx / map.width))
+            .min_by_key(|p| (p.x - from.x).pow(2) + (p.y - from.y).pow(2));
+        let to = match reachable {
+            Some(to) => to,
+            None => break,
+        };
+
+        apply_tunnel(map, rng, from, to);
+        corridors += 1;
+        eprintln!(
+            "Connectivity: {} tiles unreachable, dug a corridor from ({}, {}) to ({}, {})",
+            isolated.len(),
+            from.x,
+            from.y,
+            to.x,
+            to.y
+        );
+    }
+
+    if corridors > 0 {
+        eprintln!(
+            "Connectivity: joined isolated areas with {} corridors",
+            corridors
+        );
+    }
+}
+
+fn cull_isolated(map: &mut MapInfo, start: Point) {
+    let isolated = unreachable_floor(map, start);
+    for &idx in isolated.iter() {
+        map.tiles[idx] = TileType::Wall;
+    }
+
+    if !isolated.is_empty() {
+        eprintln!(
+            "Connectivity: walled off {} unreachable floor tiles",
+            isolated.len()
+        );
+    }
+}
+
+/// Puts a closed door wherever a one tile wide corridor enters a room.
+pub struct DoorPlacement;
+
+impl MapModifier for DoorPlacement {
+    fn modify(&mut self, map: &mut MapInfo, _rng: &mut RandomNumberGenerator) {
+        let is_wall = |map: &MapInfo, x: i32, y: i32| {
+            map.xy_idx(x, y)
+                .and_then(|idx| map.tiles.get(idx))
+                .map_or(true, |tile| tile.is_blocking())
+        };
+
+        let mut doors = Vec::new();
+        for room in map.rooms.iter() {
+            // The ring of tiles right outside the room, with the two tiles across the corridor
+            let horizontal = (room.x1..room.x2)
+                .flat_map(|x| [(x, room.y1 - 1), (x, room.y2)])
+                .map(|(x, y)| (Point::new(x, y), Point::new(x - 1, y), Point::new(x + 1, y)));
+            let vertical = (room.y1..room.y2)
+                .flat_map(|y| [(room.x1 - 1, y), (room.x2, y)])
+                .map(|(x, y)| (Point::new(x, y), Point::new(x, y - 1), Point::new(x, y + 1)));
+
+            for (door, side_a, side_b) in horizontal.chain(vertical) {
+                if !is_wall(map, door.x, door.y)
+                    && is_wall(map, side_a.x, side_a.y)
+                    && is_wall(map, side_b.x, side_b.y)
+                    && !doors.contains(&door)
+                {
+                    doors.push(door);
+                }
+            }
+        }
+        for door in doors {
+            if let Some(idx) = map.xy_idx(door.x, door.y) {
+                map.tiles[idx] = TileType::Door { open: false };
+            }
+        }
+    }
+}
+
+/// Turns patches of floor into grass using fractal noise, with tall grass in the thick of it.
+pub struct GrassScatter;
+
+impl MapModifier for GrassScatter {
+    fn modify(&mut self, map: &mut MapInfo, rng: &mut RandomNumberGenerator) {
+        let mut noise = FastNoise::seeded(rng.next_u64());
+        noise.set_noise_type(NoiseType::PerlinFractal);
+        noise.set_fractal_type(FractalType::FBM);
+        noise.set_fractal_octaves(5);
+        noise.set_fractal_gain(0.6);
+        noise.set_fractal_lacunarity(2.0);
+        noise.set_frequency(8.0);
+
+        for (idx, tile) in map.tiles.iter_mut().enumerate() {
+            if *tile != TileType::Floor {
+                continue;
+            }
+            let x = (idx % map.width) as f32;
+            let y = (idx / map.width) as f32;
+            let n = noise.get_noise(x / map.width as f32, y / map.height as f32);
+            if n < -0.35 {
+                *tile = TileType::TallGrass;
+            } else if n < 0.0 {
+                *tile = TileType::Grass;
+            }
+        }
+    }
+}
+
+/// Floods low spots with water, or lava further down, and strews rubble about.
+/// The player start, its surroundings and prefab vaults are left alone.
+pub struct TerrainFeatures {
+    pub depth: u32,
+}
+
+impl MapModifier for TerrainFeatures {
+    fn modify(&mut self, map: &mut MapInfo, rng: &mut RandomNumberGenerator) {
+        const LAVA_DEPTH: u32 = 3;
+        const SAFE_RADIUS: i32 = 3;
+
+        let mut noise = FastNoise::seeded(rng.next_u64());
+        noise.set_noise_type(NoiseType::SimplexFractal);
+        noise.set_fractal_octaves(3);
+        noise.set_frequency(6.0);
+
+        let start = map.player_start.map(|start| Position::from(&start));
+        for idx in 0..map.tiles.len() {
+            if !matches!(map.tiles[idx], TileType::Floor | TileType::Grass) {
+                continue;
+            }
+            let position = Position::new(idx % map.width, idx / map.width);
+            if start.map_or(false, |start| {
+                start.distance_squared(&position) <= SAFE_RADIUS * SAFE_RADIUS
+            }) || map
+                .vaults
+                .iter()
+                .any(|vault| vault.point_in_rect((&position).into()))
+            {
+                continue;
+            }
+
+            let x = position.x as f32 / map.width as f32;
+            let y = position.y as f32 / map.height as f32;
+            let n = noise.get_noise(x, y);
+            map.tiles[idx] = if n > 0.55 && self.depth >= LAVA_DEPTH {
+                TileType::Lava
+            } else if n > 0.55 {
+                TileType::DeepWater
+            } else if n > 0.4 {
+                TileType::ShallowWater
+            } else if rng.roll_dice(1, 50) == 1 {
+                TileType::Rubble
+            } else {
+                continue;
+            };
+        }
+    }
+}
+
+/// Mounts torches on walls facing open floor, spread out so they light different spots.
+pub struct TorchPlacement;
+
+impl MapModifier for TorchPlacement {
+    fn modify(&mut self, map: &mut MapInfo, rng: &mut RandomNumberGenerator) {
+        const FLOOR_PER_TORCH: usize = 120;
+        const MIN_SPACING: i32 = 6;
+        const ATTEMPTS: usize = 200;
+
+        let candidates = (0..map.tiles.len())
+            .filter(|&idx| map.tiles[idx] == TileType::Wall)
+            .map(|idx| Point::new(idx % map.width, idx / map.width))
+            .filter(|p| {
+                [(0, 1), (0, -1), (1, 0), (-1, 0)].iter().any(|(dx, dy)| {
+                    map.xy_idx(p.x + dx, p.y + dy)
+                        .map_or(false, |idx| map.tiles[idx] == TileType::Floor)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let wanted = map.floor_count() / FLOOR_PER_TORCH + 1;
+        let mut torches: Vec<Position> = Vec::new();
+        for _ in 0..ATTEMPTS {
+            if torches.len() >= wanted {
+                break;
+            }
+            let point = match rng.random_slice_entry(&candidates) {
+                Some(point) => *point,
+                None => break,
+            };
+            let position = Position::from(&point);
+            if torches
+                .iter()
+                .any(|torch| torch.distance_squared(&position) < MIN_SPACING * MIN_SPACING)
+            {
+                continue;
+            }
+            if let Some(idx) = map.xy_idx(point.x, point.y) {
+                map.tiles[idx] = TileType::Torch;
+                torches.push(position);
+            }
+        }
+    }
+}
+
+/// Puts the way down as far from the start as possible, and the way back up
+/// right under the player on every level but the first.
+pub struct StairsPlacement {
+    pub depth: u32,
+}
+
+impl MapModifier for StairsPlacement {
+    fn modify(&mut self, map: &mut MapInfo, _rng: &mut RandomNumberGenerator) {
+        let start = map.player_start.unwrap_or_else(Point::zero);
+        if let Some(idx) = map.farthest_floor(start).and_then(|p| map.xy_idx(p.x, p.y)) {
+            map.tiles[idx] = TileType::DownStairs;
+        }
+        if self.depth > 1 {
+            if let Some(idx) = map.xy_idx(start.x, start.y) {
+                map.tiles[idx] = TileType::UpStairs;
+            }
+        }
+    }
+}
+
+/// Mirrors or rotates the whole map, along with everything placed on it.
+/// Rotations are counterclockwise, and a quarter turn needs a square map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapTransform {
+    MirrorX,
+    MirrorY,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl MapTransform {
+    fn is_quarter_turn(&self) -> bool {
+        matches!(self, MapTransform::Rotate90 | MapTransform::Rotate270)
+    }
+
+    /// Where the cell at `p` ends up on a `width`×`height` map.
+    fn point(&self, p: Point, width: i32, height: i32) -> Point {
+        match self {
+            MapTransform::MirrorX => Point::new(width - 1 - p.x, p.y),
+            MapTransform::MirrorY => Point::new(p.x, height - 1 - p.y),
+            MapTransform::Rotate90 => Point::new(width - 1 - p.y, p.x),
+            MapTransform::Rotate180 => Point::new(width - 1 - p.x, height - 1 - p.y),
+            MapTransform::Rotate270 => Point::new(p.y, height - 1 - p.x),
+        }
+    }
+}
+
+impl FromStr for MapTransform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mirror-x" => Ok(MapTransform::MirrorX),
+            "mirror-y" => Ok(MapTransform::MirrorY),
+            "rotate-90" => Ok(MapTransform::Rotate90),
+            "rotate" | "rotate-180" => Ok(MapTransform::Rotate180),
+            "rotate-270" => Ok(MapTransform::Rotate270),
+            _ => Err(format!("Unknown map transform: {}", s)),
+        }
+    }
+}
+
+impl MapModifier for MapTransform {
+    fn modify(&mut self, map: &mut MapInfo, _rng: &mut RandomNumberGenerator) {
+        if self.is_quarter_turn() && map.width != map.height {
+            eprintln!(
+                "Transform: {:?} needs a square map, leaving the {}x{} map as it is",
+                self, map.width, map.height
+            );
+            return;
+        }
+        let (width, height) = (map.width as i32, map.height as i32);
+        let point = |p: Point| self.point(p, width, height);
+        // Rect's right and bottom edges are exclusive, so it goes by its first and last cells
+        let rect = |r: Rect| {
+            let a = point(Point::new(r.x1, r.y1));
+            let b = point(Point::new(r.x2 - 1, r.y2 - 1));
+            Rect::with_exact(
+                a.x.min(b.x),
+                a.y.min(b.y),
+                a.x.max(b.x) + 1,
+                a.y.max(b.y) + 1,
+            )
+        };
+
+        let mut tiles = map.tiles.clone();
+        for (idx, tile) in map.tiles.iter().enumerate() {
+            let to = point(Point::new(idx % map.width, idx / map.width));
+            tiles[to.y as usize * map.width + to.x as usize] = *tile;
+        }
+        map.tiles = tiles;
+
+        map.rooms = map.rooms.iter().map(|&r| rect(r)).collect();
+        map.vaults = map.vaults.iter().map(|&r| rect(r)).collect();
+        map.regions = map
+            .regions
+            .iter()
+            .map(|region| region.iter().map(|&p| point(p)).collect())
+            .collect();
+        map.spawns = map
+            .spawns
+            .iter()
+            .map(|&(p, spawn)| (point(p), spawn))
+            .collect();
+        map.player_start = map.player_start.map(point);
+    }
+}
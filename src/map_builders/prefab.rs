@@ -0,0 +1,384 @@
+use std::{collections::VecDeque, fmt, fs, path::Path};
+
+use bracket_lib::prelude::{Point, RandomNumberGenerator, Rect};
+
+use super::{MapInfo, MapModifier, Spawn};
+use crate::map_tile::TileType;
+
+/// One character of a prefab layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefabCell {
+    /// Leaves whatever the underlying map has
+    Keep,
+    Tile(TileType),
+    Spawn(Spawn),
+    PlayerStart,
+}
+
+impl PrefabCell {
+    fn from_char(c: char) -> Option<Self> {
+        let cell = match c {
+            ' ' => PrefabCell::Keep,
+            '#' => PrefabCell::Tile(TileType::Wall),
+            '.' => PrefabCell::Tile(TileType::Floor),
+            '"' => PrefabCell::Tile(TileType::Grass),
+            '+' => PrefabCell::Tile(TileType::Door { open: false }),
+            'M' => PrefabCell::Spawn(Spawn::Monster),
+            '!' => PrefabCell::Spawn(Spawn::Item),
+            '@' => PrefabCell::PlayerStart,
+            _ => return None,
+        };
+        Some(cell)
+    }
+
+    fn tile(&self) -> Option<TileType> {
+        match self {
+            PrefabCell::Keep => None,
+            PrefabCell::Tile(tile) => Some(*tile),
+            PrefabCell::Spawn(_) | PrefabCell::PlayerStart => Some(TileType::Floor),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PrefabError {
+    Io {
+        name: String,
+        error: std::io::Error,
+    },
+    Empty {
+        name: String,
+    },
+    RaggedRow {
+        name: String,
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    UnknownChar {
+        name: String,
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    SeveralPlayerStarts {
+        name: String,
+    },
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefabError::Io { name, error } => write!(f, "{}: {}", name, error),
+            PrefabError::Empty { name } => write!(f, "{}: prefab has no rows", name),
+            PrefabError::RaggedRow {
+                name,
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}:{}: row is {} characters wide, expected {}",
+                name, line, found, expected
+            ),
+            PrefabError::UnknownChar {
+                name,
+                line,
+                column,
+                found,
+            } => write!(
+                f,
+                "{}:{}:{}: unknown character {:?}, expected one of '#', '.', '\"', '+', 'M', '!', '@' or ' '",
+                name, line, column, found
+            ),
+            PrefabError::SeveralPlayerStarts { name } => {
+                write!(f, "{}: prefab has more than one player start '@'", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrefabError {}
+
+/// A hand-authored room read from an ASCII layout.
+#[derive(Debug, Clone)]
+pub struct Prefab {
+    pub name: String,
+    width: usize,
+    height: usize,
+    /// Rows from top to bottom, as written in the file
+    cells: Vec<PrefabCell>,
+}
+
+impl Prefab {
+    pub fn parse(name: &str, text: &str) -> Result<Self, PrefabError> {
+        let rows = text
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .collect::<Vec<_>>();
+        // Blank lines before and after the layout are ignored
+        let first = rows
+            .iter()
+            .position(|row| !row.trim().is_empty())
+            .ok_or_else(|| PrefabError::Empty { name: name.into() })?;
+        let last = rows
+            .iter()
+            .rposition(|row| !row.trim().is_empty())
+            .unwrap_or(first);
+        let height = last - first + 1;
+        let width = rows[first].chars().count();
+
+        let mut cells = Vec::with_capacity(width * height);
+        for (line, row) in rows.iter().enumerate().take(last + 1).skip(first) {
+            let found = row.chars().count();
+            if found != width {
+                return Err(PrefabError::RaggedRow {
+                    name: name.into(),
+                    line: line + 1,
+                    expected: width,
+                    found,
+                });
+            }
+
+            for (column, c) in row.chars().enumerate() {
+                let cell = PrefabCell::from_char(c).ok_or_else(|| PrefabError::UnknownChar {
+                    name: name.into(),
+                    line: line + 1,
+                    column: column + 1,
+                    found: c,
+                })?;
+                cells.push(cell);
+            }
+        }
+
+        let player_starts = cells
+            .iter()
+            .filter(|&&cell| cell == PrefabCell::PlayerStart)
+            .count();
+        if player_starts > 1 {
+            return Err(PrefabError::SeveralPlayerStarts { name: name.into() });
+        }
+
+        Ok(Self {
+            name: name.into(),
+            width,
+            height,
+            cells,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PrefabError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|error| PrefabError::Io {
+            name: name.clone(),
+            error,
+        })?;
+        Self::parse(&name, &text)
+    }
+
+    /// Loads every `*.txt` file in `dir`, sorted by file name so the order doesn't depend on the OS.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Vec<Result<Self, PrefabError>> {
+        let mut paths = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map_or(false, |ext| ext == "txt"))
+                .collect::<Vec<_>>(),
+            Err(_) => return Vec::new(),
+        };
+        paths.sort();
+        paths.into_iter().map(Self::load).collect()
+    }
+
+    /// Finds a free spot for the prefab and stamps it into `map`, joining it to
+    /// the rest of the level with a corridor that ends at one of its authored doors,
+    /// or at a doorway opened in its edge. Returns the occupied area.
+    pub fn stamp(&self, map: &mut MapInfo, rng: &mut RandomNumberGenerator) -> Option<Rect> {
+        const ATTEMPTS: usize = 50;
+        let (width, height) = (self.width as i32, self.height as i32);
+        if width + 2 >= map.width as i32 || height + 2 >= map.height as i32 {
+            return None;
+        }
+
+        let area = (0..ATTEMPTS)
+            .map(|_| {
+                let x = rng.range(1, map.width as i32 - width);
+                let y = rng.range(1, map.height as i32 - height);
+                Rect::with_size(x, y, width, height)
+            })
+            .find(|area| {
+                !map.rooms
+                    .iter()
+                    .chain(&map.vaults)
+                    .any(|r| r.intersect(area))
+            })?;
+
+        let entrance = map
+            .floor_points()
+            .into_iter()
+            .filter(|point| !area.point_in_rect(*point))
+            .min_by_key(|point| {
+                let center = area.center();
+                (point.x - center.x).pow(2) + (point.y - center.y).pow(2)
+            });
+
+        for (i, cell) in self.cells.iter().enumerate() {
+            let Point { x, y } = self.cell_point(&area, i);
+            let idx = y as usize * map.width + x as usize;
+            if let Some(tile) = cell.tile() {
+                map.tiles[idx] = tile;
+            }
+            match cell {
+                PrefabCell::Spawn(spawn) => map.spawns.push((Point::new(x, y), *spawn)),
+                PrefabCell::PlayerStart => map.player_start = Some(Point::new(x, y)),
+                PrefabCell::Keep | PrefabCell::Tile(_) => {}
+            }
+        }
+
+        if let Some((doorway, outside)) = entrance.and_then(|e| self.doorway(map, &area, e)) {
+            if let Some(idx) = map.xy_idx(doorway.x, doorway.y) {
+                if !map.tiles[idx].is_passable() {
+                    map.tiles[idx] = TileType::Floor;
+                }
+            }
+            dig_corridor(map, &area, outside);
+        }
+
+        map.vaults.push(area);
+        Some(area)
+    }
+
+    /// Where a cell of the layout ends up on the map. The first row of the file is the
+    /// top of the prefab, and y grows upwards.
+    fn cell_point(&self, area: &Rect, i: usize) -> Point {
+        Point::new(
+            area.x1 + (i % self.width) as i32,
+            area.y1 + (self.height - 1 - i / self.width) as i32,
+        )
+    }
+
+    /// Picks the way in on the prefab's edge closest to `target`, along with the tile just
+    /// outside it. Authored doors come first, then open edge tiles, then a wall with
+    /// floor behind it, which becomes the doorway. `Keep` cells are never used.
+    fn doorway(&self, map: &MapInfo, area: &Rect, target: Point) -> Option<(Point, Point)> {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let cell = |x: i32, y: i32| {
+            (x >= 0 && y >= 0 && x < width && y < height)
+                .then(|| self.cells[(y * width + x) as usize])
+        };
+
+        let mut candidates = Vec::new();
+        for (i, &here) in self.cells.iter().enumerate() {
+            let (cx, cy) = ((i % self.width) as i32, (i / self.width) as i32);
+            let rank = match here.tile() {
+                Some(TileType::Door { .. }) => 0,
+                Some(tile) if tile.is_passable() => 1,
+                Some(_) => 2,
+                None => continue,
+            };
+            // Layout rows go down the file while y goes up the map
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                if cell(cx + dx, cy + dy).is_some() {
+                    continue;
+                }
+                let inward = cell(cx - dx, cy - dy).and_then(|cell| cell.tile());
+                if rank == 2 && !inward.map_or(false, |tile| tile.is_passable()) {
+                    continue;
+                }
+                let point = self.cell_point(area, i);
+                let outside = Point::new(point.x + dx, point.y - dy);
+                if outside.x <= 0
+                    || outside.y <= 0
+                    || outside.x >= map.width as i32 - 1
+                    || outside.y >= map.height as i32 - 1
+                {
+                    continue;
+                }
+                let distance = (point.x - target.x).pow(2) + (point.y - target.y).pow(2);
+                candidates.push((rank, distance, point, outside));
+            }
+        }
+
+        candidates
+            .into_iter()
+            .min_by_key(|&(rank, distance, _, _)| (rank, distance))
+            .map(|(_, _, point, outside)| (point, outside))
+    }
+}
+
+/// Digs the shortest corridor from `start` to the floor already on the map, going around
+/// `area` and following existing floor where it can.
+fn dig_corridor(map: &mut MapInfo, area: &Rect, start: Point) {
+    let (width, height) = (map.width as i32, map.height as i32);
+    let mut cost = vec![usize::MAX; map.tiles.len()];
+    let mut previous = vec![None; map.tiles.len()];
+    let mut queue = VecDeque::new();
+    let start_idx = (start.y * width + start.x) as usize;
+    cost[start_idx] = 0;
+    queue.push_back(start);
+
+    // Stepping onto floor is free and digging costs one, so this is a 0-1 BFS
+    let mut end = None;
+    while let Some(point) = queue.pop_front() {
+        let idx = (point.y * width + point.x) as usize;
+        if point != start && map.tiles[idx].is_passable() {
+            end = Some(point);
+            break;
+        }
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let next = Point::new(point.x + dx, point.y + dy);
+            if next.x <= 0
+                || next.y <= 0
+                || next.x >= width - 1
+                || next.y >= height - 1
+                || area.point_in_rect(next)
+            {
+                continue;
+            }
+            let next_idx = (next.y * width + next.x) as usize;
+            let step = usize::from(!map.tiles[next_idx].is_passable());
+            if cost[idx] + step < cost[next_idx] {
+                cost[next_idx] = cost[idx] + step;
+                previous[next_idx] = Some(point);
+                if step == 0 {
+                    queue.push_front(next);
+                } else {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    let mut point = match end {
+        Some(end) => previous[(end.y * width + end.x) as usize],
+        None => return,
+    };
+    while let Some(here) = point {
+        let idx = (here.y * width + here.x) as usize;
+        if !map.tiles[idx].is_passable() {
+            map.tiles[idx] = TileType::Floor;
+        }
+        point = previous[idx];
+    }
+}
+
+/// Stamps prefabs on top of whatever the previous builders produced.
+pub struct PrefabStamp {
+    prefabs: Vec<Prefab>,
+}
+
+impl PrefabStamp {
+    pub fn new(prefabs: Vec<Prefab>) -> Self {
+        Self { prefabs }
+    }
+}
+
+impl MapModifier for PrefabStamp {
+    fn modify(&mut self, map: &mut MapInfo, rng: &mut RandomNumberGenerator) {
+        for prefab in self.prefabs.iter() {
+            if prefab.stamp(map, rng).is_none() {
+                eprintln!("No room left for prefab {}", prefab.name);
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use bracket_lib::prelude::{Point, RandomNumberGenerator, Rect};
+
+use super::{apply_room, apply_tunnel, MapBuilder, MapInfo};
+
+pub struct RoomMapBuilder {
+    width: usize,
+    height: usize,
+    rooms: Vec<Rect>,
+    min_room_size: usize,
+    max_room_size: usize,
+}
+
+impl RoomMapBuilder {
+    pub fn new(width: usize, height: usize) -> Self {
+        let min_room_size = 4;
+        let max_room_size = (width / 3).min(height / 3).max(min_room_size);
+        assert!(min_room_size <= max_room_size);
+        Self {
+            width,
+            height,
+            min_room_size,
+            max_room_size,
+            rooms: Vec::new(),
+        }
+    }
+}
+
+impl MapBuilder for RoomMapBuilder {
+    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapInfo {
+        const MAX_ROOMS: usize = 30;
+        let mut map = MapInfo::new(self.width, self.height);
+        for _ in 0..MAX_ROOMS {
+            let room = self.build_random_room(rng);
+            if self.rooms.iter().any(|r| r.intersect(&room)) {
+                continue;
+            }
+
+            apply_room(&mut map, &room);
+            if let Some(prev) = self.rooms.last() {
+                apply_tunnel(&mut map, rng, prev.center(), room.center());
+            }
+            self.rooms.push(room);
+        }
+
+        let player_pos = self
+            .rooms
+            .get(0)
+            .map(|r| r.center())
+            .unwrap_or_else(Point::zero);
+
+        map.player_start = Some(player_pos);
+        map.rooms = self.rooms.clone();
+        map
+    }
+}
+
+impl RoomMapBuilder {
+    fn build_random_room(&self, rng: &mut RandomNumberGenerator) -> Rect {
+        let w = rng.range(self.min_room_size, self.max_room_size + 1);
+        let h = rng.range(self.min_room_size, self.max_room_size + 1);
+        let x = rng.range(2, self.width - w - 1) - 1;
+        let y = rng.range(2, self.height - h - 1) - 1;
+
+        Rect::with_size(x, y, w, h)
+    }
+}
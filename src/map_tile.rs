@@ -1,16 +1,37 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::EntityCommands, prelude::*};
 use bevy_ascii_terminal::Tile;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    combat::Dice,
     components::{Blocker, Layer, Opaque, Position},
+    light::LightSource,
     LAYER_MAP,
 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
+    /// A wall with a torch mounted on it
+    Torch,
     Floor,
     Grass,
+    TallGrass,
+    ShallowWater,
+    DeepWater,
+    Lava,
+    Rubble,
+    DownStairs,
+    UpStairs,
+    Door {
+        open: bool,
+    },
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stairs {
+    Down,
+    Up,
 }
 
 impl From<TileType> for Tile {
@@ -27,6 +48,11 @@ impl From<&TileType> for Tile {
                 bg_color: Color::BLACK,
                 fg_color: Color::SEA_GREEN,
             },
+            TileType::Torch => Tile {
+                glyph: '☼',
+                bg_color: Color::BLACK,
+                fg_color: Color::GOLD,
+            },
             TileType::Floor => Tile {
                 glyph: '.',
                 bg_color: Color::BLACK,
@@ -37,6 +63,51 @@ impl From<&TileType> for Tile {
                 bg_color: Color::BLACK,
                 fg_color: Color::DARK_GREEN,
             },
+            TileType::TallGrass => Tile {
+                glyph: '♣',
+                bg_color: Color::BLACK,
+                fg_color: Color::DARK_GREEN,
+            },
+            TileType::ShallowWater => Tile {
+                glyph: '~',
+                bg_color: Color::BLACK,
+                fg_color: Color::rgb(0.3, 0.6, 1.0),
+            },
+            TileType::DeepWater => Tile {
+                glyph: '≈',
+                bg_color: Color::rgb(0.0, 0.1, 0.4),
+                fg_color: Color::rgb(0.2, 0.4, 1.0),
+            },
+            TileType::Lava => Tile {
+                glyph: '≈',
+                bg_color: Color::rgb(0.4, 0.05, 0.0),
+                fg_color: Color::ORANGE_RED,
+            },
+            TileType::Rubble => Tile {
+                glyph: ':',
+                bg_color: Color::BLACK,
+                fg_color: Color::GRAY,
+            },
+            TileType::DownStairs => Tile {
+                glyph: '>',
+                bg_color: Color::BLACK,
+                fg_color: Color::CYAN,
+            },
+            TileType::UpStairs => Tile {
+                glyph: '<',
+                bg_color: Color::BLACK,
+                fg_color: Color::CYAN,
+            },
+            TileType::Door { open: false } => Tile {
+                glyph: '+',
+                bg_color: Color::BLACK,
+                fg_color: Color::rgb(0.6, 0.4, 0.2),
+            },
+            TileType::Door { open: true } => Tile {
+                glyph: '\'',
+                bg_color: Color::BLACK,
+                fg_color: Color::rgb(0.6, 0.4, 0.2),
+            },
         }
     }
 }
@@ -45,39 +116,174 @@ impl TileType {
     pub fn as_name(&self) -> Name {
         match self {
             TileType::Wall => "Wall".into(),
+            TileType::Torch => "Torch".into(),
             TileType::Floor => "Floor".into(),
             TileType::Grass => "Grass".into(),
+            TileType::TallGrass => "Tall grass".into(),
+            TileType::ShallowWater => "Shallow water".into(),
+            TileType::DeepWater => "Deep water".into(),
+            TileType::Lava => "Lava".into(),
+            TileType::Rubble => "Rubble".into(),
+            TileType::DownStairs => "Stairs down".into(),
+            TileType::UpStairs => "Stairs up".into(),
+            TileType::Door { open: false } => "Closed door".into(),
+            TileType::Door { open: true } => "Open door".into(),
         }
     }
 
     pub fn is_blocking(&self) -> bool {
         match self {
-            TileType::Wall => true,
-            TileType::Floor | TileType::Grass => false,
+            TileType::Wall | TileType::Torch | TileType::Door { open: false } => true,
+            TileType::Floor
+            | TileType::Grass
+            | TileType::TallGrass
+            | TileType::ShallowWater
+            | TileType::DeepWater
+            | TileType::Lava
+            | TileType::Rubble
+            | TileType::DownStairs
+            | TileType::UpStairs
+            | TileType::Door { open: true } => false,
         }
     }
 
     pub fn is_opaque(&self) -> bool {
         match self {
-            TileType::Wall => true,
-            TileType::Floor | TileType::Grass => false,
+            TileType::Wall
+            | TileType::Torch
+            | TileType::Door { open: false }
+            | TileType::TallGrass => true,
+            TileType::Floor
+            | TileType::Grass
+            | TileType::ShallowWater
+            | TileType::DeepWater
+            | TileType::Lava
+            | TileType::Rubble
+            | TileType::DownStairs
+            | TileType::UpStairs
+            | TileType::Door { open: true } => false,
+        }
+    }
+
+    /// Whether something can get through the tile, maybe after opening it first.
+    pub fn is_passable(&self) -> bool {
+        !self.is_blocking() || self.is_door()
+    }
+
+    pub fn is_door(&self) -> bool {
+        matches!(self, TileType::Door { .. })
+    }
+
+    /// Energy it takes to step onto the tile, a plain move costs 100.
+    pub fn move_cost(&self) -> i32 {
+        match self {
+            TileType::ShallowWater => 150,
+            TileType::DeepWater | TileType::Rubble => 200,
+            _ => 100,
+        }
+    }
+
+    /// Cost of stepping onto the tile for path finding: the time it takes,
+    /// plus a penalty that keeps monsters out of harm's way when there's another route.
+    pub fn path_cost(&self) -> f32 {
+        let danger = match self {
+            TileType::Lava => 20.0,
+            TileType::DeepWater => 4.0,
+            _ => 0.0,
+        };
+        self.move_cost() as f32 / 100.0 + danger
+    }
+
+    /// Damage dealt every turn to whoever stands on the tile.
+    pub fn damage(&self) -> Option<Dice> {
+        match self {
+            TileType::Lava => Some(Dice::from((1, 6, 2))),
+            _ => None,
+        }
+    }
+
+    /// Percent chance that a move off the tile fails.
+    pub fn slip_chance(&self) -> i32 {
+        match self {
+            TileType::ShallowWater => 10,
+            TileType::DeepWater => 25,
+            _ => 0,
+        }
+    }
+
+    /// Percent chance every turn that whoever stands on the tile goes under for a moment.
+    pub fn drown_chance(&self) -> i32 {
+        match self {
+            TileType::DeepWater => 20,
+            _ => 0,
+        }
+    }
+
+    pub fn light(&self) -> Option<LightSource> {
+        match self {
+            TileType::Torch => Some(LightSource {
+                range: 5,
+                intensity: 0.9,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn is_hazard(&self) -> bool {
+        self.damage().is_some() || self.drown_chance() > 0
+    }
+
+    pub fn stairs(&self) -> Option<Stairs> {
+        match self {
+            TileType::DownStairs => Some(Stairs::Down),
+            TileType::UpStairs => Some(Stairs::Up),
+            TileType::Wall
+            | TileType::Torch
+            | TileType::Floor
+            | TileType::Grass
+            | TileType::TallGrass
+            | TileType::ShallowWater
+            | TileType::DeepWater
+            | TileType::Lava
+            | TileType::Rubble
+            | TileType::Door { .. } => None,
         }
     }
 
     pub fn spawn(&self, commands: &mut Commands, position: Position) -> Entity {
         let mut entity = commands.spawn();
+        entity.insert(position).insert(Layer(LAYER_MAP));
+        self.insert_into(&mut entity);
+
+        entity.id()
+    }
+
+    /// Inserts every component that depends on the tile type, removing the ones that
+    /// no longer apply, so an already spawned tile can change type in place.
+    pub fn insert_into(&self, entity: &mut EntityCommands) {
         entity
             .insert(Tile::from(self))
-            .insert(position)
             .insert(self.as_name())
-            .insert(Layer(LAYER_MAP));
+            .insert(*self);
         if self.is_blocking() {
             entity.insert(Blocker);
+        } else {
+            entity.remove::<Blocker>();
         }
         if self.is_opaque() {
             entity.insert(Opaque);
+        } else {
+            entity.remove::<Opaque>();
+        }
+        if let Some(light) = self.light() {
+            entity.insert(light);
+        } else {
+            entity.remove::<LightSource>();
+        }
+        if let Some(stairs) = self.stairs() {
+            entity.insert(stairs);
+        } else {
+            entity.remove::<Stairs>();
         }
-
-        entity.id()
     }
 }
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy_ascii_terminal::Tile;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{Fov, Layer, Player, Position, Unrevealable, Visible},
+    light::LightMap,
+    monster::Monster,
+    save::{name_format, tile_format},
+    update_visibility,
+};
+
+/// Where the player last saw a monster or an item, and what it looked like then.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Ghost {
+    pub position: Position,
+    #[serde(with = "tile_format")]
+    pub tile: Tile,
+    pub layer: Layer,
+    #[serde(with = "name_format")]
+    pub name: Name,
+    pub threat: bool,
+}
+
+/// The monsters and items the player remembers on the current level. Monsters and
+/// items are `Unrevealable`, so once out of sight they're only drawn from here.
+/// Levels the player has left keep theirs in [`crate::dungeon::StoredLevel`].
+#[derive(Default)]
+pub struct Memory {
+    ghosts: HashMap<Entity, Ghost>,
+    /// Ghosts of things that were gone by the time the level was left
+    strays: Vec<Ghost>,
+}
+
+impl Memory {
+    pub fn ghost(&self, entity: Entity) -> Option<&Ghost> {
+        self.ghosts.get(&entity)
+    }
+
+    /// Every ghost, with the entity it stands for unless that one is gone.
+    pub fn ghosts(&self) -> impl Iterator<Item = (Option<Entity>, &Ghost)> {
+        self.ghosts
+            .iter()
+            .map(|(&entity, ghost)| (Some(entity), ghost))
+            .chain(self.strays.iter().map(|ghost| (None, ghost)))
+    }
+
+    /// Puts back a ghost stored along with its level.
+    pub fn remember(&mut self, entity: Option<Entity>, ghost: Ghost) {
+        match entity {
+            Some(entity) => {
+                self.ghosts.insert(entity, ghost);
+            }
+            None => self.strays.push(ghost),
+        }
+    }
+
+    /// Takes the ghost of `entity` out, to store it along with the entity.
+    pub fn take(&mut self, entity: Entity) -> Option<Ghost> {
+        self.ghosts.remove(&entity)
+    }
+
+    /// Forgets the whole level, returning the ghosts no entity was stored with.
+    pub fn take_strays(&mut self) -> Vec<Ghost> {
+        let mut strays = std::mem::take(&mut self.strays);
+        strays.extend(self.ghosts.drain().map(|(_, ghost)| ghost));
+        strays
+    }
+}
+
+pub struct MemoryPlugin;
+
+impl Plugin for MemoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Memory>()
+            // Before the level is stored away with what's remembered of it, as the restored
+            // ghosts can't be checked against the player's sight until it's worked out again
+            .add_system(
+                update_memory
+                    .after(update_visibility)
+                    .before("render_map")
+                    .before("change_level"),
+            );
+    }
+}
+
+/// Records what the player sees, and forgets a ghost once its tile is back in sight
+/// and whatever was there has gone.
+fn update_memory(
+    mut memory: ResMut<Memory>,
+    light_map: Res<LightMap>,
+    player: Query<(&Fov, &Position), With<Player>>,
+    seen: Query<
+        (Entity, &Position, &Tile, &Layer, &Name, Option<&Monster>),
+        (With<Unrevealable>, With<Visible>, Without<Player>),
+    >,
+    positions: Query<&Position>,
+) {
+    let (fov, player_pos) = match player.get_single() {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    for (entity, &position, &tile, &layer, name, monster) in seen.iter() {
+        memory.ghosts.insert(
+            entity,
+            Ghost {
+                position,
+                tile,
+                layer,
+                name: name.clone(),
+                threat: monster.is_some(),
+            },
+        );
+    }
+
+    let in_sight = |position: &Position| {
+        fov.visible_tiles.contains(position)
+            && (position == player_pos || light_map.is_lit(position))
+    };
+    memory.ghosts.retain(|&entity, ghost| {
+        !in_sight(&ghost.position) || positions.get(entity).ok() == Some(&ghost.position)
+    });
+    memory.strays.retain(|ghost| !in_sight(&ghost.position));
+}
@@ -0,0 +1,288 @@
+use bevy::{app::AppExit, prelude::*};
+use bevy_ascii_terminal::{StringFormat, Terminal};
+use bracket_lib::prelude::RandomNumberGenerator;
+
+use crate::{
+    components::MapViewTerminal,
+    events::{LoadGameEvent, NewRunEvent},
+    fov::{FovAlgorithm, FovSettings},
+    map_builders::MapBuilderKind,
+    resources::{Difficulty, GameState},
+    save::SaveSettings,
+    settings::{launch_option, MapSettings},
+    VIEWPORT_SIZE,
+};
+
+/// Longest seed that can be typed in, any 19 digit number fits in a `u64`.
+const MAX_SEED_DIGITS: usize = 19;
+
+/// What the player has entered on the setup screen so far.
+#[derive(Default)]
+pub struct RunSetup {
+    pub seed: String,
+    pub difficulty: Difficulty,
+    selected: usize,
+}
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunSetup>()
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu)
+                    .with_system(main_menu.after("render_map")),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::RunSetup).with_system(reset_run_setup))
+            .add_system_set(
+                SystemSet::on_update(GameState::RunSetup)
+                    .with_system(run_setup_menu.after("render_map")),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(settings_menu.after("render_map")),
+            );
+    }
+}
+
+/// A menu entry: its text and whether it can be picked.
+struct Entry {
+    text: String,
+    enabled: bool,
+}
+
+impl Entry {
+    fn new<T: Into<String>>(text: T) -> Self {
+        Self {
+            text: text.into(),
+            enabled: true,
+        }
+    }
+
+    fn disabled<T: Into<String>>(text: T) -> Self {
+        Self {
+            text: text.into(),
+            enabled: false,
+        }
+    }
+}
+
+/// Moves the selection with the arrow keys, wrapping around at both ends.
+fn move_selection(input: &Input<KeyCode>, selected: &mut usize, count: usize) {
+    if input.just_pressed(KeyCode::Up) || input.just_pressed(KeyCode::Numpad8) {
+        *selected = (*selected + count - 1) % count;
+    }
+    if input.just_pressed(KeyCode::Down) || input.just_pressed(KeyCode::Numpad2) {
+        *selected = (*selected + 1) % count;
+    }
+}
+
+/// Steps through `options` with the left and right arrows.
+fn cycle<T: Copy + PartialEq>(input: &Input<KeyCode>, current: T, options: &[T]) -> T {
+    let index = options
+        .iter()
+        .position(|&option| option == current)
+        .unwrap_or(0);
+    if input.just_pressed(KeyCode::Left) || input.just_pressed(KeyCode::Numpad4) {
+        options[(index + options.len() - 1) % options.len()]
+    } else if input.just_pressed(KeyCode::Right)
+        || input.just_pressed(KeyCode::Numpad6)
+        || input.just_pressed(KeyCode::Return)
+    {
+        options[(index + 1) % options.len()]
+    } else {
+        current
+    }
+}
+
+/// Draws a menu in the middle of the map terminal, over whatever was there.
+fn draw_menu(terminal: &mut Terminal, title: &str, entries: &[Entry], selected: usize, hint: &str) {
+    let width = 40;
+    let height = entries.len() as i32 * 2 + 5;
+    let x = (VIEWPORT_SIZE[0] as i32 - width) / 2;
+    let y = (VIEWPORT_SIZE[1] as i32 - height) / 2;
+    terminal.clear();
+    terminal.draw_box_double([x, y], [width as u32, height as u32]);
+    terminal.put_string_formatted(
+        [x + 3, y + height - 1],
+        title,
+        StringFormat::colors(Color::YELLOW, Color::NONE),
+    );
+    terminal.put_string_formatted(
+        [x + 3, y],
+        hint,
+        StringFormat::colors(Color::YELLOW, Color::NONE),
+    );
+    for (i, entry) in entries.iter().enumerate() {
+        let (marker, fg) = match (i == selected, entry.enabled) {
+            (true, true) => ("> ", Color::YELLOW),
+            (false, true) => ("  ", Color::WHITE),
+            (true, false) => ("> ", Color::GRAY),
+            (false, false) => ("  ", Color::DARK_GRAY),
+        };
+        terminal.put_string_formatted(
+            [x + 3, y + height - 3 - i as i32 * 2],
+            &format!("{}{}", marker, entry.text),
+            StringFormat::colors(fg, Color::NONE),
+        );
+    }
+}
+
+fn main_menu(
+    mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
+    mut input: ResMut<Input<KeyCode>>,
+    mut states: ResMut<State<GameState>>,
+    mut load_events: EventWriter<LoadGameEvent>,
+    mut exit_events: EventWriter<AppExit>,
+    mut selected: Local<usize>,
+    save_settings: Res<SaveSettings>,
+) {
+    let entries = [
+        Entry::new("New Game"),
+        if save_settings.path.exists() {
+            Entry::new("Continue")
+        } else {
+            Entry::disabled("Continue")
+        },
+        Entry::new("Settings"),
+        Entry::new("Quit"),
+    ];
+    move_selection(&input, &mut selected, entries.len());
+
+    if input.just_pressed(KeyCode::Return) && entries[*selected].enabled {
+        input.clear();
+        match *selected {
+            0 => states.set(GameState::RunSetup).unwrap(),
+            // Switches to gameplay once the save is loaded, a broken one leaves us here
+            1 => load_events.send(LoadGameEvent),
+            2 => states.set(GameState::Settings).unwrap(),
+            _ => exit_events.send(AppExit),
+        }
+    }
+
+    draw_menu(
+        &mut terminal.single_mut(),
+        "Roguelike",
+        &entries,
+        *selected,
+        "ARROWS to pick, ENTER to select",
+    );
+}
+
+/// Starts every visit to the setup screen from the `--seed` option, if one was given.
+fn reset_run_setup(mut setup: ResMut<RunSetup>) {
+    *setup = RunSetup {
+        seed: launch_option("--seed", "ROGUELIKE_SEED").unwrap_or_default(),
+        ..default()
+    };
+}
+
+fn run_setup_menu(
+    mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
+    mut input: ResMut<Input<KeyCode>>,
+    mut char_evr: EventReader<ReceivedCharacter>,
+    mut states: ResMut<State<GameState>>,
+    mut new_run_events: EventWriter<NewRunEvent>,
+    mut setup: ResMut<RunSetup>,
+) {
+    const SEED: usize = 0;
+    const DIFFICULTY: usize = 1;
+    const START: usize = 2;
+
+    move_selection(&input, &mut setup.selected, 3);
+    match setup.selected {
+        SEED => {
+            for c in char_evr.iter().map(|r| r.char) {
+                if c.is_ascii_digit() && setup.seed.len() < MAX_SEED_DIGITS {
+                    setup.seed.push(c);
+                }
+            }
+            if input.just_pressed(KeyCode::Back) {
+                setup.seed.pop();
+            }
+        }
+        DIFFICULTY => setup.difficulty = cycle(&input, setup.difficulty, &Difficulty::ALL),
+        _ => {}
+    }
+
+    if input.just_pressed(KeyCode::Escape) {
+        input.clear();
+        states.set(GameState::MainMenu).unwrap();
+        return;
+    }
+    if input.just_pressed(KeyCode::Return) && setup.selected == START {
+        input.clear();
+        let seed = setup
+            .seed
+            .parse()
+            .unwrap_or_else(|_| RandomNumberGenerator::new().next_u64());
+        new_run_events.send(NewRunEvent {
+            seed,
+            difficulty: setup.difficulty,
+        });
+    }
+
+    let seed = if setup.seed.is_empty() {
+        "random".to_string()
+    } else {
+        setup.seed.clone()
+    };
+    let entries = [
+        Entry::new(format!("Seed: {}", seed)),
+        Entry::new(format!("Difficulty: < {} >", setup.difficulty.name())),
+        Entry::new("Start"),
+    ];
+    draw_menu(
+        &mut terminal.single_mut(),
+        "New game",
+        &entries,
+        setup.selected,
+        "Type a seed, ESCAPE to go back",
+    );
+}
+
+fn settings_menu(
+    mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
+    mut input: ResMut<Input<KeyCode>>,
+    mut states: ResMut<State<GameState>>,
+    mut fov_settings: ResMut<FovSettings>,
+    mut map_settings: ResMut<MapSettings>,
+    mut selected: Local<usize>,
+) {
+    const FOV: usize = 0;
+    const BUILDER: usize = 1;
+
+    move_selection(&input, &mut selected, 3);
+    match *selected {
+        FOV => fov_settings.algorithm = cycle(&input, fov_settings.algorithm, &FovAlgorithm::ALL),
+        BUILDER => map_settings.builder = cycle(&input, map_settings.builder, &MapBuilderKind::ALL),
+        _ => {
+            if input.just_pressed(KeyCode::Return) {
+                input.clear();
+                states.set(GameState::MainMenu).unwrap();
+                return;
+            }
+        }
+    }
+    if input.just_pressed(KeyCode::Escape) {
+        input.clear();
+        states.set(GameState::MainMenu).unwrap();
+        return;
+    }
+
+    let entries = [
+        Entry::new(format!(
+            "Field of view: < {} >",
+            fov_settings.algorithm.name()
+        )),
+        Entry::new(format!("Map builder: < {} >", map_settings.builder.name())),
+        Entry::new("Back"),
+    ];
+    draw_menu(
+        &mut terminal.single_mut(),
+        "Settings",
+        &entries,
+        *selected,
+        "ARROWS to change, ESCAPE to go back",
+    );
+}
@@ -1,14 +1,13 @@
 use bevy::prelude::*;
 use bevy_ascii_terminal::Tile;
-use bracket_lib::prelude::{
-    a_star_search, Algorithm2D, Bresenham, DistanceAlg, RandomNumberGenerator,
-};
+use bracket_lib::prelude::{a_star_search, Algorithm2D, Point};
 
 use crate::{
     combat::{Attack, CombatStatsBundle, Health},
-    components::{Blocker, Energy, MovingEntityBundle, Player, TakingATurn, WantToMove},
+    components::{Blocker, Energy, Fov, MovingEntityBundle, Player, TakingATurn, WantToMove},
     map::Map,
-    Layer, Position, Unrevealable, LAYER_MONSTER,
+    raws::MonsterDef,
+    update_fov, Layer, Position, Unrevealable, Visible, LAYER_MONSTER,
 };
 
 #[derive(Component)]
@@ -18,6 +17,7 @@ pub struct Monster;
 pub struct MonsterBundle {
     pub monster: Monster,
     pub name: Name,
+    pub fov: Fov,
     pub unrevealable: Unrevealable,
     pub blocker: Blocker,
     pub tile: Tile,
@@ -33,6 +33,7 @@ impl Default for MonsterBundle {
         Self {
             monster: Monster,
             name: "Goblin".into(),
+            fov: Fov::new(6),
             unrevealable: Unrevealable,
             blocker: Blocker,
             tile: Tile {
@@ -54,59 +55,51 @@ pub struct MonsterPlugin;
 
 impl Plugin for MonsterPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(monster_ai);
+        app.add_system(monster_ai.after(update_fov));
     }
 }
 
-pub fn spawn_monster(commands: &mut Commands, position: Position) -> Entity {
-    let mut rng = RandomNumberGenerator::new();
-    let roll = rng.roll_dice(1, 6);
-    let (glyph, name, attack, speed) = match roll {
-        1 => ('o', "Orc", Attack::new((1, 6)), 30),
-        _ => ('g', "Goblin", Attack::new((1, 4)), 45),
-    };
+pub fn spawn_monster(commands: &mut Commands, def: &MonsterDef, position: Position) -> Entity {
     let monster = MonsterBundle {
-        monster: Monster,
-        name: name.into(),
-        tile: Tile {
-            glyph,
-            fg_color: Color::RED,
-            bg_color: Color::BLACK,
-        },
+        name: Name::new(def.name.clone()),
+        fov: Fov::new(def.fov_range),
+        tile: def.tile,
         combat_stats: CombatStatsBundle {
-            health: Health::new(10),
-            attack,
+            health: Health::new(def.health),
+            attack: Attack::new(def.attack),
         },
-        moving: MovingEntityBundle::new(speed).with_position(position),
+        moving: MovingEntityBundle::new(def.speed).with_position(position),
         ..Default::default()
     };
-    commands.spawn_bundle(monster).id()
+    let mut entity = commands.spawn_bundle(monster);
+    if let Some(light) = def.light {
+        entity.insert(light);
+    }
+    entity.id()
 }
 
+/// Monsters chase the player when they can see them. Sight goes both ways: a monster
+/// the player can see sees the player too, even if its own view doesn't reach that far.
 pub fn monster_ai(
     mut commands: Commands,
     map: Res<Map>,
     player: Query<&Position, With<Player>>,
-    mut monsters: Query<(Entity, &Position, &mut Energy), (With<Monster>, With<TakingATurn>)>,
+    mut monsters: Query<
+        (Entity, &Position, &Fov, Option<&Visible>, &mut Energy),
+        (With<Monster>, With<TakingATurn>),
+    >,
 ) {
     let player_pos = match player.get_single() {
         Ok(pos) => pos,
         Err(_) => return,
     };
-    let vision_distance_squared = 36.0; // TODO
-    for (monster_entity, monster_pos, mut energy) in monsters.iter_mut() {
+    for (monster_entity, monster_pos, fov, visible, mut energy) in monsters.iter_mut() {
         energy.0 = 0;
-        let player_pos = player_pos.into();
-        let monster_pos = monster_pos.into();
-        if DistanceAlg::PythagorasSquared.distance2d(player_pos, monster_pos)
-            > vision_distance_squared
-        {
-            continue;
-        }
-        let mut line = Bresenham::new(player_pos, monster_pos);
-        if line.any(|p| map.opaque.contains(&p.into())) {
+        if visible.is_none() && !fov.visible_tiles.contains(player_pos) {
             continue;
         }
+        let player_pos = Point::from(player_pos);
+        let monster_pos = Point::from(monster_pos);
 
         let path = a_star_search(
             map.point2d_to_index(monster_pos) as i32,
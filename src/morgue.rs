@@ -0,0 +1,209 @@
+use std::{
+    fmt::Write,
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*, window::WindowCloseRequested};
+use bevy_ascii_terminal::Tile;
+
+use crate::{
+    combat::Health,
+    components::{Player, Position, Revealed},
+    inventory::backpack_items,
+    items::InBackpack,
+    log::GameLog,
+    map::Map,
+    map_tile::TileType,
+    replay::Replay,
+    resources::{Depth, GameState, RunStats},
+    rng::GameRng,
+    settings::launch_option,
+};
+
+/// How many of the last log messages go into a morgue file.
+const MORGUE_LOG_LINES: usize = 50;
+
+/// Where morgue files are written, `--morgue <dir>` or `ROGUELIKE_MORGUE`.
+pub struct MorgueSettings {
+    pub dir: PathBuf,
+}
+
+impl MorgueSettings {
+    pub fn from_env() -> Self {
+        Self {
+            dir: launch_option("--morgue", "ROGUELIKE_MORGUE")
+                .unwrap_or_else(|| "morgue".to_string())
+                .into(),
+        }
+    }
+}
+
+pub struct MorguePlugin;
+
+impl Plugin for MorguePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MorgueSettings::from_env())
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(morgue_on_death))
+            .add_system(morgue_on_quit);
+    }
+}
+
+/// Everything a morgue file is made of.
+#[derive(SystemParam)]
+struct RunSummary<'w, 's> {
+    rng: Res<'w, GameRng>,
+    stats: Res<'w, RunStats>,
+    depth: Res<'w, Depth>,
+    game_log: ResMut<'w, GameLog>,
+    map: Res<'w, Map>,
+    players: Query<'w, 's, (Entity, &'static Position, &'static Health), With<Player>>,
+    backpack: Query<'w, 's, (Entity, &'static InBackpack, &'static Name)>,
+    tiles: Query<'w, 's, (&'static Position, &'static TileType), With<Revealed>>,
+}
+
+impl<'w, 's> RunSummary<'w, 's> {
+    fn render(&self, outcome: &str) -> String {
+        let mut text = String::new();
+        let player = self.players.get_single().ok();
+
+        writeln!(text, "Roguelike morgue file").unwrap();
+        writeln!(text).unwrap();
+        writeln!(text, "Seed: {}", self.rng.seed()).unwrap();
+        writeln!(text, "Outcome: {}", outcome).unwrap();
+        writeln!(text, "Depth reached: {}", self.depth.0).unwrap();
+        writeln!(text, "Turns: {}", self.stats.turns).unwrap();
+        if let Some((_, _, health)) = player {
+            writeln!(text, "Health: {}/{}", health.current, health.max).unwrap();
+        }
+        writeln!(text, "Kills: {}", self.stats.total_kills()).unwrap();
+
+        writeln!(text, "\nBackpack").unwrap();
+        let items = match player {
+            Some((player, _, _)) => backpack_items(player, self.backpack.iter()),
+            None => Vec::new(),
+        };
+        if items.is_empty() {
+            writeln!(text, "  (empty)").unwrap();
+        }
+        for (_, name) in items {
+            writeln!(text, "  {}", name).unwrap();
+        }
+
+        writeln!(text, "\nKills by monster").unwrap();
+        if self.stats.kills.is_empty() {
+            writeln!(text, "  (none)").unwrap();
+        }
+        for (name, count) in self.stats.kills.iter() {
+            writeln!(text, "  {:<24} {}", name, count).unwrap();
+        }
+
+        writeln!(text, "\nLast messages").unwrap();
+        let entries = self.game_log.entries();
+        for entry in entries
+            .iter()
+            .skip(entries.len().saturating_sub(MORGUE_LOG_LINES))
+        {
+            writeln!(text, "  {}", entry).unwrap();
+        }
+
+        writeln!(text, "\nMap, depth {}", self.depth.0).unwrap();
+        for row in self.map_rows(player.map(|(_, position, _)| *position)) {
+            writeln!(text, "{}", row).unwrap();
+        }
+
+        text
+    }
+
+    /// The revealed part of the level from top to bottom, since y grows upwards in the game.
+    fn map_rows(&self, player: Option<Position>) -> Vec<String> {
+        let (width, height) = (self.map.width(), self.map.height());
+        let mut glyphs = vec![' '; width * height];
+        let index = |position: &Position| {
+            (position.x >= 0
+                && position.y >= 0
+                && (position.x as usize) < width
+                && (position.y as usize) < height)
+                .then(|| position.y as usize * width + position.x as usize)
+        };
+        for (position, tile_type) in self.tiles.iter() {
+            if let Some(idx) = index(position) {
+                glyphs[idx] = Tile::from(tile_type).glyph;
+            }
+        }
+        if let Some(idx) = player.as_ref().and_then(index) {
+            glyphs[idx] = '@';
+        }
+
+        let mut rows = glyphs
+            .chunks(width.max(1))
+            .rev()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<_>>();
+        // Only the part that was explored
+        while rows.last().map_or(false, |row| row.is_empty()) {
+            rows.pop();
+        }
+        let top = rows.iter().take_while(|row| row.is_empty()).count();
+        rows.split_off(top)
+    }
+
+    fn write(&self, settings: &MorgueSettings, outcome: &str) -> std::io::Result<PathBuf> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        fs::create_dir_all(&settings.dir)?;
+        let path = settings
+            .dir
+            .join(format!("morgue-{}-{}.txt", self.rng.seed(), timestamp));
+        fs::write(&path, self.render(outcome))?;
+        Ok(path)
+    }
+}
+
+fn morgue_on_death(
+    mut summary: RunSummary,
+    replay: Option<Res<Replay>>,
+    settings: Res<MorgueSettings>,
+) {
+    // A replay isn't a run of its own
+    if replay.is_some() {
+        return;
+    }
+    let outcome = summary
+        .stats
+        .cause_of_death
+        .clone()
+        .unwrap_or_else(|| "Died".to_string());
+    match summary.write(&settings, &outcome) {
+        Ok(path) => summary
+            .game_log
+            .push(format!("Morgue file written to {}", path.display())),
+        Err(err) => eprintln!("Failed to write the morgue file: {}", err),
+    }
+}
+
+/// Quitting in the middle of a run, by closing the window or from a menu, ends it too.
+/// The run is saved on the way out, so the morgue file is where it stood then.
+fn morgue_on_quit(
+    mut close_events: EventReader<WindowCloseRequested>,
+    mut exit_events: EventReader<AppExit>,
+    mut written: Local<bool>,
+    state: Res<State<GameState>>,
+    replay: Option<Res<Replay>>,
+    summary: RunSummary,
+    settings: Res<MorgueSettings>,
+) {
+    // Closing the window sends both, so only one of them writes the file
+    let quitting = close_events.iter().count() + exit_events.iter().count() > 0;
+    if !quitting || *written || !state.current().is_in_play() || replay.is_some() {
+        return;
+    }
+    *written = true;
+    let outcome = format!("Quit on turn {}", summary.stats.turns);
+    if let Err(err) = summary.write(&settings, &outcome) {
+        eprintln!("Failed to write the morgue file: {}", err);
+    }
+}
@@ -4,6 +4,8 @@ use bevy_ascii_terminal::Tile;
 use crate::{
     combat::{Attack, CombatStatsBundle, Health},
     components::{Fov, Layer, MovingEntityBundle, Player, Position},
+    light::Lantern,
+    resources::Difficulty,
     LAYER_PLAYER,
 };
 
@@ -12,6 +14,7 @@ pub struct PlayerBundle {
     pub player: Player,
     pub name: Name,
     pub fov: Fov,
+    pub lantern: Lantern,
     pub tile: Tile,
     pub layer: Layer,
     #[bundle]
@@ -25,7 +28,9 @@ impl Default for PlayerBundle {
         Self {
             player: Player,
             name: "Player".into(),
-            fov: Fov::new(8),
+            // Sight reaches further than the lantern, to spot lit rooms across the dark
+            fov: Fov::new(12),
+            lantern: Lantern::default(),
             tile: Tile {
                 glyph: '@',
                 fg_color: Color::WHITE,
@@ -41,8 +46,16 @@ impl Default for PlayerBundle {
     }
 }
 
-pub fn spawn_player(commands: &mut Commands, position: Position) -> Entity {
+pub fn spawn_player(commands: &mut Commands, position: Position, difficulty: Difficulty) -> Entity {
     let player = PlayerBundle {
+        lantern: Lantern {
+            fuel: difficulty.lantern_fuel(),
+            ..default()
+        },
+        combat_stats: CombatStatsBundle {
+            health: Health::new(difficulty.player_health()),
+            attack: Attack::new((1, 6)),
+        },
         moving: MovingEntityBundle::new(50).with_position(position),
         ..default()
     };
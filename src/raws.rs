@@ -0,0 +1,282 @@
+use std::{collections::HashSet, fmt, fs, path::Path};
+
+use bevy::prelude::*;
+use bevy_ascii_terminal::Tile;
+use serde::Deserialize;
+
+use crate::{
+    combat::Dice, components::Position, items::spawn_item, light::LightSource, map_builders::Spawn,
+    monster::spawn_monster, settings::launch_option, spawn_table::SpawnTable,
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFile {
+    #[serde(default)]
+    monsters: Vec<MonsterRaw>,
+    #[serde(default)]
+    items: Vec<ItemRaw>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MonsterRaw {
+    name: String,
+    glyph: char,
+    fg: String,
+    bg: Option<String>,
+    health: u32,
+    attack: String,
+    speed: i32,
+    fov_range: u32,
+    light: Option<LightSource>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ItemRaw {
+    name: String,
+    glyph: char,
+    fg: String,
+    bg: Option<String>,
+    #[serde(default)]
+    effects: ItemEffects,
+}
+
+/// What using an item does.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ItemEffects {
+    pub heal: Option<u32>,
+    pub fuel: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonsterDef {
+    pub name: String,
+    pub tile: Tile,
+    pub health: u32,
+    pub attack: Dice,
+    pub speed: i32,
+    pub fov_range: u32,
+    pub light: Option<LightSource>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ItemDef {
+    pub name: String,
+    pub tile: Tile,
+    pub effects: ItemEffects,
+}
+
+#[derive(Debug)]
+pub enum RawsError {
+    Io {
+        name: String,
+        error: std::io::Error,
+    },
+    Parse {
+        name: String,
+        error: serde_json::Error,
+    },
+    Invalid {
+        name: String,
+        definition: String,
+        message: String,
+    },
+    Duplicate {
+        name: String,
+        definition: String,
+    },
+}
+
+impl fmt::Display for RawsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawsError::Io { name, error } => write!(f, "{}: {}", name, error),
+            RawsError::Parse { name, error } => write!(f, "{}: {}", name, error),
+            RawsError::Invalid {
+                name,
+                definition,
+                message,
+            } => write!(f, "{}: {:?}: {}", name, definition, message),
+            RawsError::Duplicate { name, definition } => {
+                write!(f, "{}: {:?} is defined more than once", name, definition)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RawsError {}
+
+/// Monster and item definitions, loaded from a data file so new ones don't need code.
+#[derive(Debug, Clone, Default)]
+pub struct Raws {
+    pub monsters: Vec<MonsterDef>,
+    pub items: Vec<ItemDef>,
+}
+
+impl Raws {
+    pub fn parse(name: &str, text: &str) -> Result<Self, RawsError> {
+        let file: RawFile = serde_json::from_str(text).map_err(|error| RawsError::Parse {
+            name: name.into(),
+            error,
+        })?;
+
+        let invalid = |definition: &str, message: String| RawsError::Invalid {
+            name: name.into(),
+            definition: definition.into(),
+            message,
+        };
+
+        let mut names = HashSet::new();
+        let mut check_name = |definition: &str| {
+            if definition.is_empty() {
+                Err(invalid(definition, "name is empty".into()))
+            } else if !names.insert(definition.to_string()) {
+                Err(RawsError::Duplicate {
+                    name: name.into(),
+                    definition: definition.into(),
+                })
+            } else {
+                Ok(())
+            }
+        };
+
+        let mut raws = Raws::default();
+        for monster in file.monsters {
+            check_name(&monster.name)?;
+            let tile = parse_tile(
+                monster.glyph,
+                &monster.fg,
+                monster.bg.as_deref(),
+                Color::BLACK,
+            )
+            .map_err(|message| invalid(&monster.name, message))?;
+            let attack = monster
+                .attack
+                .parse()
+                .map_err(|message| invalid(&monster.name, message))?;
+            if monster.health == 0 {
+                return Err(invalid(&monster.name, "health must be above 0".into()));
+            }
+            if monster.speed <= 0 {
+                return Err(invalid(&monster.name, "speed must be above 0".into()));
+            }
+            if let Some(light) = monster.light {
+                if light.range == 0 || !(light.intensity > 0.0 && light.intensity <= 1.0) {
+                    return Err(invalid(
+                        &monster.name,
+                        "light needs a range above 0 and an intensity in (0, 1]".into(),
+                    ));
+                }
+            }
+            raws.monsters.push(MonsterDef {
+                name: monster.name,
+                tile,
+                health: monster.health,
+                attack,
+                speed: monster.speed,
+                fov_range: monster.fov_range,
+                light: monster.light,
+            });
+        }
+
+        for item in file.items {
+            check_name(&item.name)?;
+            let tile = parse_tile(item.glyph, &item.fg, item.bg.as_deref(), Color::NONE)
+                .map_err(|message| invalid(&item.name, message))?;
+            if item.effects.heal == Some(0) {
+                return Err(invalid(&item.name, "heal must be above 0".into()));
+            }
+            if item.effects.fuel == Some(0) {
+                return Err(invalid(&item.name, "fuel must be above 0".into()));
+            }
+            raws.items.push(ItemDef {
+                name: item.name,
+                tile,
+                effects: item.effects,
+            });
+        }
+
+        Ok(raws)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RawsError> {
+        let path = path.as_ref();
+        let name = path.display().to_string();
+        let text = fs::read_to_string(path).map_err(|error| RawsError::Io {
+            name: name.clone(),
+            error,
+        })?;
+        Self::parse(&name, &text)
+    }
+
+    /// Loads the file given by `--raws <path>` or `ROGUELIKE_RAWS`, `assets/raws.json` by default,
+    /// and makes sure it defines everything the dungeon's spawn table asks for.
+    pub fn from_env() -> Result<Self, RawsError> {
+        let path = launch_option("--raws", "ROGUELIKE_RAWS")
+            .unwrap_or_else(|| "assets/raws.json".to_string());
+        let raws = Self::load(&path)?;
+        raws.check_spawn_table(&path, &SpawnTable::dungeon())?;
+        Ok(raws)
+    }
+
+    /// Fails on the first spawn table entry with no definition of the right kind, so a typo
+    /// shows up at startup rather than whenever the entry happens to be rolled.
+    pub fn check_spawn_table(&self, name: &str, table: &SpawnTable) -> Result<(), RawsError> {
+        for entry in table.entries() {
+            let (defined, kind) = match entry.kind {
+                Spawn::Monster => (self.monster(&entry.name).is_some(), "monster"),
+                Spawn::Item => (self.item(&entry.name).is_some(), "item"),
+            };
+            if !defined {
+                return Err(RawsError::Invalid {
+                    name: name.into(),
+                    definition: entry.name.clone(),
+                    message: format!("the spawn table needs this {}, but it isn't defined", kind),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn monster(&self, name: &str) -> Option<&MonsterDef> {
+        self.monsters.iter().find(|monster| monster.name == name)
+    }
+
+    pub fn item(&self, name: &str) -> Option<&ItemDef> {
+        self.items.iter().find(|item| item.name == name)
+    }
+}
+
+fn parse_tile(glyph: char, fg: &str, bg: Option<&str>, default_bg: Color) -> Result<Tile, String> {
+    let parse_color = |field: &str, hex: &str| {
+        Color::hex(hex.trim_start_matches('#')).map_err(|_| {
+            format!(
+                "`{}` is not a hex colour like \"#ff0000\": {:?}",
+                field, hex
+            )
+        })
+    };
+    Ok(Tile {
+        glyph,
+        fg_color: parse_color("fg", fg)?,
+        bg_color: bg.map_or(Ok(default_bg), |bg| parse_color("bg", bg))?,
+    })
+}
+
+/// Spawns the monster or item defined under `name`, if there is such a definition.
+pub fn spawn_named(
+    commands: &mut Commands,
+    raws: &Raws,
+    name: &str,
+    position: Position,
+) -> Option<Entity> {
+    if let Some(monster) = raws.monster(name) {
+        Some(spawn_monster(commands, monster, position))
+    } else {
+        raws.item(name)
+            .map(|item| spawn_item(commands, item, position))
+    }
+}
@@ -0,0 +1,422 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use bevy_ascii_terminal::{StringFormat, Terminal};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::MapViewTerminal,
+    events::{ActionTakenEvent, LoadGameEvent, NewRunEvent, PlayerAction},
+    fov::{FovAlgorithm, FovSettings},
+    log::GameLog,
+    map_builders::{MapBuilderKind, MapTransform},
+    raws::Raws,
+    resources::{Difficulty, GameState},
+    settings::{launch_option, MapSettings},
+    turn::PlayerTurn,
+    VIEWPORT_SIZE,
+};
+
+/// Bumped whenever the recording layout changes, so old recordings are refused.
+pub const RECORDING_VERSION: u32 = 3;
+
+/// How long a replay waits between two actions when it isn't fast-forwarding.
+const REPLAY_STEP: f32 = 0.2;
+
+/// First line of a recording: everything needed to build the same run again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub version: u32,
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub builder: MapBuilderKind,
+    pub width: usize,
+    pub height: usize,
+    pub transform: Option<MapTransform>,
+    pub fov: FovAlgorithm,
+    pub prefabs_dir: String,
+    pub assets: AssetHashes,
+}
+
+/// Hashes of the data levels are generated and populated from. Playing a recording back
+/// with different data would build other levels, so it's refused instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetHashes {
+    pub raws: u64,
+    pub prefabs: u64,
+    pub spawn_table: u64,
+}
+
+impl AssetHashes {
+    pub fn new(raws: &Raws, map_settings: &MapSettings) -> Self {
+        Self {
+            raws: hash(raws),
+            prefabs: hash(&map_settings.prefabs),
+            spawn_table: hash(&map_settings.spawn_table),
+        }
+    }
+
+    /// Names of the data that differs between `self` and `other`.
+    fn differences(&self, other: &AssetHashes) -> Vec<&'static str> {
+        [
+            ("raws", self.raws == other.raws),
+            ("prefabs", self.prefabs == other.prefabs),
+            ("spawn table", self.spawn_table == other.spawn_table),
+        ]
+        .into_iter()
+        .filter(|(_, same)| !same)
+        .map(|(name, _)| name)
+        .collect()
+    }
+}
+
+/// FNV-1a of the debug output, which unlike `DefaultHasher` is the same on every build.
+fn hash<T: fmt::Debug>(value: &T) -> u64 {
+    format!("{:?}", value)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version {
+        found: u32,
+    },
+    Assets {
+        differences: Vec<&'static str>,
+        prefabs_dir: String,
+    },
+    Empty,
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::Io(error) => write!(f, "{}", error),
+            RecordingError::Format(error) => write!(f, "not a valid recording: {}", error),
+            RecordingError::Version { found } => write!(
+                f,
+                "the recording is format version {}, this game only reads version {}",
+                found, RECORDING_VERSION
+            ),
+            RecordingError::Assets {
+                differences,
+                prefabs_dir,
+            } => write!(
+                f,
+                "the recording was made with other {} (prefabs from {:?})",
+                differences.join(", "),
+                prefabs_dir
+            ),
+            RecordingError::Empty => write!(f, "the recording is empty"),
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+impl From<std::io::Error> for RecordingError {
+    fn from(error: std::io::Error) -> Self {
+        RecordingError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for RecordingError {
+    fn from(error: serde_json::Error) -> Self {
+        RecordingError::Format(error)
+    }
+}
+
+/// A recorded run, stored as JSON lines: the header, then one line per action.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub actions: Vec<PlayerAction>,
+}
+
+impl Recording {
+    pub fn parse(text: &str) -> Result<Self, RecordingError> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let header: RecordingHeader =
+            serde_json::from_str(lines.next().ok_or(RecordingError::Empty)?)?;
+        if header.version != RECORDING_VERSION {
+            return Err(RecordingError::Version {
+                found: header.version,
+            });
+        }
+        let actions = lines
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<PlayerAction>, _>>()?;
+        Ok(Self { header, actions })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RecordingError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Fails if the recording was made with other raws, prefabs or spawn table.
+    pub fn check_assets(&self, assets: &AssetHashes) -> Result<(), RecordingError> {
+        let differences = self.header.assets.differences(assets);
+        if differences.is_empty() {
+            Ok(())
+        } else {
+            Err(RecordingError::Assets {
+                differences,
+                prefabs_dir: self.header.prefabs_dir.clone(),
+            })
+        }
+    }
+}
+
+/// Where runs are recorded to, `--record <path>` or `ROGUELIKE_RECORD`.
+pub struct RecordSettings {
+    pub path: PathBuf,
+}
+
+impl RecordSettings {
+    pub fn from_env() -> Self {
+        Self {
+            path: launch_option("--record", "ROGUELIKE_RECORD")
+                .unwrap_or_else(|| "recording.jsonl".to_string())
+                .into(),
+        }
+    }
+}
+
+/// The recording of the current run, if it's being recorded.
+#[derive(Default)]
+struct Recorder {
+    file: Option<BufWriter<File>>,
+}
+
+impl Recorder {
+    fn write<T: Serialize>(&mut self, line: &T) -> Result<(), RecordingError> {
+        if let Some(file) = self.file.as_mut() {
+            serde_json::to_writer(&mut *file, line)?;
+            writeln!(file)?;
+            // Flushed every time, so the recording survives a crash
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// A recording being played back instead of the keyboard.
+pub struct Replay {
+    actions: Vec<PlayerAction>,
+    next: usize,
+    paused: bool,
+    fast: bool,
+    started: bool,
+    timer: Timer,
+}
+
+impl Replay {
+    pub fn new(actions: Vec<PlayerAction>) -> Self {
+        Self {
+            actions,
+            next: 0,
+            paused: false,
+            fast: false,
+            started: false,
+            timer: Timer::from_seconds(REPLAY_STEP, false),
+        }
+    }
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RecordSettings::from_env())
+            .init_resource::<Recorder>()
+            .add_startup_system(start_replay)
+            .add_system(record.after("perform_player_action"))
+            .add_system(drive_replay.before("perform_player_action"))
+            .add_system(render_replay_status.after("render_map"));
+    }
+}
+
+/// Starts the run from `--replay <path>` or `ROGUELIKE_REPLAY`, if one was given.
+fn start_replay(
+    mut commands: Commands,
+    mut new_run_events: EventWriter<NewRunEvent>,
+    mut map_settings: ResMut<MapSettings>,
+    mut fov_settings: ResMut<FovSettings>,
+    raws: Res<Raws>,
+) {
+    let path = match launch_option("--replay", "ROGUELIKE_REPLAY") {
+        Some(path) => path,
+        None => return,
+    };
+    let assets = AssetHashes::new(&raws, &map_settings);
+    let recording = Recording::load(Path::new(&path))
+        .and_then(|recording| recording.check_assets(&assets).map(|_| recording));
+    let recording = match recording {
+        Ok(recording) => recording,
+        Err(err) => {
+            eprintln!("Failed to load the recording {}: {}", path, err);
+            return;
+        }
+    };
+
+    let header = recording.header;
+    map_settings.builder = header.builder;
+    map_settings.width = header.width;
+    map_settings.height = header.height;
+    map_settings.transform = header.transform;
+    fov_settings.algorithm = header.fov;
+    commands.insert_resource(Replay::new(recording.actions));
+    new_run_events.send(NewRunEvent {
+        seed: header.seed,
+        difficulty: header.difficulty,
+    });
+}
+
+/// Writes every run started from the setup screen to the recording file. Loading a save
+/// ends the recording, since what follows can't be played back from the seed.
+#[allow(clippy::too_many_arguments)]
+fn record(
+    mut recorder: ResMut<Recorder>,
+    mut new_run_events: EventReader<NewRunEvent>,
+    mut load_events: EventReader<LoadGameEvent>,
+    mut taken_events: EventReader<ActionTakenEvent>,
+    replay: Option<Res<Replay>>,
+    settings: Res<RecordSettings>,
+    map_settings: Res<MapSettings>,
+    fov_settings: Res<FovSettings>,
+    raws: Res<Raws>,
+) {
+    if load_events.iter().last().is_some() {
+        recorder.file = None;
+    }
+    if let Some(event) = new_run_events.iter().last() {
+        recorder.file = None;
+        if replay.is_none() {
+            let header = RecordingHeader {
+                version: RECORDING_VERSION,
+                seed: event.seed,
+                difficulty: event.difficulty,
+                builder: map_settings.builder,
+                width: map_settings.width,
+                height: map_settings.height,
+                transform: map_settings.transform,
+                fov: fov_settings.algorithm,
+                prefabs_dir: map_settings.prefabs_dir.clone(),
+                assets: AssetHashes::new(&raws, &map_settings),
+            };
+            match File::create(&settings.path) {
+                Ok(file) => recorder.file = Some(BufWriter::new(file)),
+                Err(err) => {
+                    eprintln!("Failed to record to {}: {}", settings.path.display(), err)
+                }
+            }
+            if let Err(err) = recorder.write(&header) {
+                eprintln!("Failed to record to {}: {}", settings.path.display(), err);
+                recorder.file = None;
+            }
+        }
+    }
+
+    for ActionTakenEvent(action) in taken_events.iter() {
+        if let Err(err) = recorder.write(action) {
+            eprintln!("Failed to record to {}: {}", settings.path.display(), err);
+            recorder.file = None;
+        }
+    }
+}
+
+/// Feeds the recorded actions to the player, one per turn. `P` pauses, `N` steps one
+/// action while paused and `F` fast-forwards.
+#[allow(clippy::too_many_arguments)]
+fn drive_replay(
+    mut commands: Commands,
+    mut replay: Option<ResMut<Replay>>,
+    mut game_log: ResMut<GameLog>,
+    mut actions: EventWriter<PlayerAction>,
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    state: Res<State<GameState>>,
+    turn: PlayerTurn,
+) {
+    let replay = match replay.as_mut() {
+        Some(replay) => replay,
+        None => return,
+    };
+    if !state.current().is_in_play() {
+        // The recorded player died, or the replay was left from the menus
+        if replay.started {
+            commands.remove_resource::<Replay>();
+        }
+        return;
+    }
+    replay.started = true;
+
+    if input.just_pressed(KeyCode::P) {
+        replay.paused = !replay.paused;
+    }
+    if input.just_pressed(KeyCode::F) {
+        replay.fast = !replay.fast;
+    }
+    replay.timer.tick(time.delta());
+
+    if !turn.is_waiting() {
+        return;
+    }
+    let due = if replay.paused {
+        input.just_pressed(KeyCode::N)
+    } else {
+        replay.fast || replay.timer.finished()
+    };
+    if !due {
+        return;
+    }
+    replay.timer.reset();
+
+    if let Some(&action) = replay.actions.get(replay.next) {
+        actions.send(action);
+        replay.next += 1;
+    }
+
+    if replay.next >= replay.actions.len() {
+        game_log.push("Replay finished, the keyboard is yours");
+        commands.remove_resource::<Replay>();
+    }
+}
+
+fn render_replay_status(
+    mut terminal: Query<&mut Terminal, With<MapViewTerminal>>,
+    replay: Option<Res<Replay>>,
+) {
+    let replay = match replay {
+        Some(replay) => replay,
+        None => return,
+    };
+    let mode = if replay.paused {
+        "paused, N: step"
+    } else if replay.fast {
+        "fast"
+    } else {
+        "playing"
+    };
+    let status = format!(
+        " Replay {}/{} {} - P: pause, F: fast ",
+        replay.next,
+        replay.actions.len(),
+        mode
+    );
+    terminal.single_mut().put_string_formatted(
+        [0, VIEWPORT_SIZE[1] as i32 - 1],
+        &status,
+        StringFormat::colors(Color::YELLOW, Color::BLACK),
+    );
+}
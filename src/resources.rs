@@ -1,6 +1,93 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub enum GameState {
+    MainMenu,
+    RunSetup,
+    Settings,
     Gameplay,
     Inventory,
     DropItemMenu,
+    GameOver,
+}
+
+impl GameState {
+    /// Whether a run is going on, as opposed to the menus and the death screen.
+    pub fn is_in_play(&self) -> bool {
+        matches!(
+            self,
+            GameState::Gameplay | GameState::Inventory | GameState::DropItemMenu
+        )
+    }
+}
+
+/// How deep in the dungeon the current level is, starting from 1.
+pub struct Depth(pub u32);
+
+impl Default for Depth {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+/// How the current run has gone so far, shown on the death screen.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct RunStats {
+    /// Turns the player has taken.
+    pub turns: u32,
+    /// Monsters the player has killed, by name.
+    pub kills: BTreeMap<String, u32>,
+    pub cause_of_death: Option<String>,
+}
+
+impl RunStats {
+    pub fn total_kills(&self) -> u32 {
+        self.kills.values().sum()
+    }
+}
+
+/// Picked on the setup screen before a run starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::Normal
+    }
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// Hit points the player starts with.
+    pub fn player_health(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 30,
+            Difficulty::Normal => 20,
+            Difficulty::Hard => 14,
+        }
+    }
+
+    /// Fuel in the lantern at the start, out of its usual 500.
+    pub fn lantern_fuel(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 500,
+            Difficulty::Normal => 500,
+            Difficulty::Hard => 250,
+        }
+    }
 }
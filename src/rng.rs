@@ -0,0 +1,66 @@
+use std::ops::{Deref, DerefMut};
+
+use bracket_lib::prelude::RandomNumberGenerator;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::launch_option;
+
+/// The single random number generator shared by map generation, spawning and combat.
+/// Two runs started with the same seed and fed the same inputs play out the same way.
+/// Saves store the whole generator, so loading one carries on the exact same sequence.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct GameRng {
+    seed: u64,
+    rng: RandomNumberGenerator,
+}
+
+impl GameRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: RandomNumberGenerator::seeded(seed),
+        }
+    }
+
+    /// Takes the seed from `--seed <n>`, then from the `ROGUELIKE_SEED` environment variable,
+    /// and falls back to a random one, saying so when the given seed isn't a number.
+    pub fn from_env() -> Self {
+        let seed = launch_option("--seed", "ROGUELIKE_SEED")
+            .and_then(|seed| match seed.parse() {
+                Ok(seed) => Some(seed),
+                Err(_) => {
+                    eprintln!(
+                        "--seed expects a number, got {:?}, using a random seed",
+                        seed
+                    );
+                    None
+                }
+            })
+            .unwrap_or_else(|| RandomNumberGenerator::new().next_u64());
+        Self::seeded(seed)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+impl Deref for GameRng {
+    type Target = RandomNumberGenerator;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rng
+    }
+}
+
+impl DerefMut for GameRng {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.rng
+    }
+}
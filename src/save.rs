@@ -0,0 +1,544 @@
+use std::{collections::HashMap, fmt, fs, path::PathBuf};
+
+use bevy::prelude::*;
+use bevy_ascii_terminal::Tile;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{Attack, Health},
+    components::{Energy, Fov, Layer, Player, Position, Revealed, Speed, Unrevealable},
+    dungeon::{Dungeon, StoredTile},
+    events::{LoadGameEvent, MapReplacedEvent, SaveGameEvent},
+    fov::FovAlgorithm,
+    items::{Fuel, InBackpack, Item, Potion},
+    light::{Lantern, LightSource},
+    log::GameLog,
+    map::{spawn_map_root, Map, MapRoot},
+    map_tile::TileType,
+    memory::Memory,
+    monster::{Monster, MonsterBundle},
+    player::PlayerBundle,
+    replay::Replay,
+    resources::{Depth, GameState, RunStats},
+    rng::GameRng,
+    settings::launch_option,
+    turn::PlayerTurn,
+    LAYER_ITEM,
+};
+
+/// Bumped whenever the save layout changes, so old saves are refused instead of misread.
+pub const SAVE_VERSION: u32 = 4;
+
+/// Where the game is saved to and loaded from, `--save <path>` or `ROGUELIKE_SAVE`.
+pub struct SaveSettings {
+    pub path: PathBuf,
+}
+
+impl SaveSettings {
+    pub fn from_env() -> Self {
+        Self {
+            path: launch_option("--save", "ROGUELIKE_SAVE")
+                .unwrap_or_else(|| "savegame.json".to_string())
+                .into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    Version { found: u32 },
+    Invalid(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{}", error),
+            SaveError::Format(error) => write!(f, "not a valid save: {}", error),
+            SaveError::Version { found } => write!(
+                f,
+                "the save is format version {}, this game only reads version {}",
+                found, SAVE_VERSION
+            ),
+            SaveError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(error: std::io::Error) -> Self {
+        SaveError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> Self {
+        SaveError::Format(error)
+    }
+}
+
+/// Serde helpers for `Name`, which has no serde support of its own.
+pub mod name_format {
+    use bevy::prelude::Name;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(name: &Name, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(name.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Name, D::Error> {
+        String::deserialize(deserializer).map(Name::new)
+    }
+}
+
+/// Serde helpers for the terminal's `Tile`, stored as a glyph and two RGBA colours.
+pub mod tile_format {
+    use bevy::prelude::Color;
+    use bevy_ascii_terminal::Tile;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    struct SavedTile {
+        glyph: char,
+        fg: [f32; 4],
+        bg: [f32; 4],
+    }
+
+    pub fn serialize<S: Serializer>(tile: &Tile, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedTile {
+            glyph: tile.glyph,
+            fg: tile.fg_color.as_rgba_f32(),
+            bg: tile.bg_color.as_rgba_f32(),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tile, D::Error> {
+        let tile = SavedTile::deserialize(deserializer)?;
+        Ok(Tile {
+            glyph: tile.glyph,
+            fg_color: Color::from(tile.fg),
+            bg_color: Color::from(tile.bg),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum EntityKind {
+    Player,
+    Monster,
+    Item,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedFov {
+    pub range: u32,
+    pub algorithm: Option<FovAlgorithm>,
+}
+
+/// The player, a monster or an item with the components that matter to gameplay.
+/// Anything the systems add on the fly, like `Visible` or `TakingATurn`, is left out.
+#[derive(Serialize, Deserialize)]
+pub struct SavedEntity {
+    /// The entity's id when it was saved, only used to link entities to each other.
+    pub id: u32,
+    pub kind: EntityKind,
+    #[serde(with = "name_format")]
+    pub name: Name,
+    #[serde(with = "tile_format")]
+    pub tile: Tile,
+    pub position: Option<Position>,
+    pub health: Option<Health>,
+    pub attack: Option<Attack>,
+    pub energy: Option<i32>,
+    pub speed: Option<i32>,
+    pub fov: Option<SavedFov>,
+    pub lantern: Option<Lantern>,
+    pub light: Option<LightSource>,
+    pub potion: Option<u32>,
+    pub fuel: Option<u32>,
+    /// Saved id of the entity carrying this one.
+    pub in_backpack: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// A whole run: the current level as it is, the levels left behind and the state
+/// shared between them.
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+    pub rng: GameRng,
+    pub depth: u32,
+    pub log: Vec<String>,
+    pub stats: RunStats,
+    pub width: usize,
+    pub height: usize,
+    pub tiles: Vec<StoredTile>,
+    pub entities: Vec<SavedEntity>,
+    pub dungeon: Dungeon,
+}
+
+impl SaveGame {
+    /// Checks the version before anything else, so an old save gets a clear error
+    /// rather than whatever field happens to fail first.
+    pub fn parse(text: &str) -> Result<Self, SaveError> {
+        let header: SaveHeader = serde_json::from_str(text)?;
+        if header.version != SAVE_VERSION {
+            return Err(SaveError::Version {
+                found: header.version,
+            });
+        }
+        let save: SaveGame = serde_json::from_str(text)?;
+        save.validate()?;
+        Ok(save)
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, SaveError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: &std::path::Path) -> Result<(), SaveError> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), SaveError> {
+        let players = self
+            .entities
+            .iter()
+            .filter(|entity| entity.kind == EntityKind::Player)
+            .count();
+        if players != 1 {
+            return Err(SaveError::Invalid(format!(
+                "expected one player, found {}",
+                players
+            )));
+        }
+        let ids = self
+            .entities
+            .iter()
+            .map(|entity| entity.id)
+            .collect::<Vec<_>>();
+        for entity in self.entities.iter() {
+            if let Some(owner) = entity.in_backpack {
+                if !ids.contains(&owner) {
+                    return Err(SaveError::Invalid(format!(
+                        "{} is carried by entity {}, which isn't in the save",
+                        entity.name, owner
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawns the saved level into the world. Entities get new ids, so backpack owners
+    /// are looked up again once everything exists.
+    pub fn restore(self, commands: &mut Commands) {
+        commands.insert_resource(Map::new(self.width, self.height));
+
+        let tile_entities = self
+            .tiles
+            .into_iter()
+            .map(|tile| {
+                let entity = tile.tile_type.spawn(commands, tile.position);
+                if tile.revealed {
+                    commands.entity(entity).insert(Revealed);
+                }
+                entity
+            })
+            .collect::<Vec<_>>();
+        spawn_map_root(commands, &tile_entities);
+
+        let mut spawned = HashMap::new();
+        let mut carried = Vec::new();
+        for saved in self.entities {
+            if let Some(owner) = saved.in_backpack {
+                carried.push((saved.id, owner));
+            }
+            let id = saved.id;
+            spawned.insert(id, saved.spawn(commands));
+        }
+        for (item, owner) in carried {
+            commands.entity(spawned[&item]).insert(InBackpack {
+                owner: spawned[&owner],
+            });
+        }
+    }
+}
+
+impl SavedEntity {
+    fn spawn(self, commands: &mut Commands) -> Entity {
+        let mut entity = match self.kind {
+            EntityKind::Player => commands.spawn_bundle(PlayerBundle::default()),
+            EntityKind::Monster => commands.spawn_bundle(MonsterBundle::default()),
+            EntityKind::Item => {
+                let mut entity = commands.spawn();
+                entity
+                    .insert(Item)
+                    .insert(Layer(LAYER_ITEM))
+                    .insert(Unrevealable);
+                entity
+            }
+        };
+        entity.insert(self.name).insert(self.tile);
+
+        match self.position {
+            Some(position) => entity.insert(position),
+            // Bundles come with a position, carried items have none
+            None => entity.remove::<Position>(),
+        };
+        if let Some(health) = self.health {
+            entity.insert(health);
+        }
+        if let Some(attack) = self.attack {
+            entity.insert(attack);
+        }
+        if let Some(energy) = self.energy {
+            entity.insert(Energy(energy));
+        }
+        if let Some(speed) = self.speed {
+            entity.insert(Speed(speed));
+        }
+        if let Some(fov) = self.fov {
+            let mut restored = Fov::new(fov.range);
+            restored.algorithm = fov.algorithm;
+            entity.insert(restored);
+        }
+        if let Some(lantern) = self.lantern {
+            entity.insert(lantern);
+        }
+        if let Some(light) = self.light {
+            entity.insert(light);
+        }
+        if let Some(heal_amount) = self.potion {
+            entity.insert(Potion { heal_amount });
+        }
+        if let Some(amount) = self.fuel {
+            entity.insert(Fuel { amount });
+        }
+        entity.id()
+    }
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SaveSettings::from_env())
+            .add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .add_system_set(SystemSet::on_update(GameState::Gameplay).with_system(save_keys))
+            .add_system(save_on_close.before(save_game))
+            .add_system(save_game.label("save_game"))
+            .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(delete_save))
+            // First thing in the frame, before anything holds on to the entities it replaces
+            .add_system_to_stage(CoreStage::First, load_game);
+    }
+}
+
+fn save_keys(
+    input: Res<Input<KeyCode>>,
+    mut save_events: EventWriter<SaveGameEvent>,
+    mut load_events: EventWriter<LoadGameEvent>,
+    replay: Option<Res<Replay>>,
+    turn: PlayerTurn,
+) {
+    if replay.is_some() {
+        return;
+    }
+    // Who is in the middle of their turn isn't saved, so only save between turns
+    if input.just_pressed(KeyCode::F5) && turn.is_waiting() {
+        save_events.send(SaveGameEvent);
+    }
+    if input.just_pressed(KeyCode::F9) {
+        load_events.send(LoadGameEvent);
+    }
+}
+
+fn save_on_close(
+    mut close_events: EventReader<bevy::window::WindowCloseRequested>,
+    mut save_events: EventWriter<SaveGameEvent>,
+) {
+    if close_events.iter().next().is_some() {
+        save_events.send(SaveGameEvent);
+    }
+}
+
+/// A run that ended in death is over, so Continue has nothing left to pick up.
+fn delete_save(replay: Option<Res<Replay>>, settings: Res<SaveSettings>) {
+    // A replay dying says nothing about the player's own run
+    if replay.is_some() || !settings.path.exists() {
+        return;
+    }
+    if let Err(err) = fs::remove_file(&settings.path) {
+        eprintln!(
+            "Failed to delete the save {}: {}",
+            settings.path.display(),
+            err
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn save_game(
+    mut events: EventReader<SaveGameEvent>,
+    mut game_log: ResMut<GameLog>,
+    rng: Res<GameRng>,
+    state: Res<State<GameState>>,
+    replay: Option<Res<Replay>>,
+    settings: Res<SaveSettings>,
+    map: Res<Map>,
+    depth: Res<Depth>,
+    stats: Res<RunStats>,
+    dungeon: Res<Dungeon>,
+    tiles: Query<(&Position, &TileType, Option<&Revealed>)>,
+    entities: Query<
+        (
+            (Entity, &Name, &Tile, Option<&Player>, Option<&Monster>),
+            (
+                Option<&Position>,
+                Option<&Health>,
+                Option<&Attack>,
+                Option<&Energy>,
+                Option<&Speed>,
+                Option<&Fov>,
+                Option<&Lantern>,
+                Option<&LightSource>,
+            ),
+            (Option<&Potion>, Option<&Fuel>, Option<&InBackpack>),
+        ),
+        Or<(With<Player>, With<Monster>, With<Item>)>,
+    >,
+) {
+    // Nothing to save from the menus, and a run that's over can't be picked up again.
+    // Nor a replay, which would overwrite the player's own save
+    if events.iter().last().is_none() || !state.current().is_in_play() || replay.is_some() {
+        return;
+    }
+
+    let entities = entities
+        .iter()
+        .map(
+            |(
+                (entity, name, &tile, player, monster),
+                (position, health, attack, energy, speed, fov, lantern, light),
+                (potion, fuel, in_backpack),
+            )| SavedEntity {
+                id: entity.id(),
+                kind: match (player, monster) {
+                    (Some(_), _) => EntityKind::Player,
+                    (None, Some(_)) => EntityKind::Monster,
+                    (None, None) => EntityKind::Item,
+                },
+                name: name.clone(),
+                tile,
+                position: position.copied(),
+                health: health.copied(),
+                attack: attack.copied(),
+                energy: energy.map(|energy| energy.0),
+                speed: speed.map(|speed| speed.0),
+                fov: fov.map(|fov| SavedFov {
+                    range: fov.range,
+                    algorithm: fov.algorithm,
+                }),
+                lantern: lantern.copied(),
+                light: light.copied(),
+                potion: potion.map(|potion| potion.heal_amount),
+                fuel: fuel.map(|fuel| fuel.amount),
+                in_backpack: in_backpack.map(|in_backpack| in_backpack.owner.id()),
+            },
+        )
+        .collect::<Vec<_>>();
+    if !entities
+        .iter()
+        .any(|entity| entity.kind == EntityKind::Player)
+    {
+        return;
+    }
+
+    let save = SaveGame {
+        version: SAVE_VERSION,
+        rng: rng.clone(),
+        depth: depth.0,
+        log: game_log.entries().clone(),
+        stats: stats.clone(),
+        width: map.width(),
+        height: map.height(),
+        tiles: tiles
+            .iter()
+            .map(|(&position, &tile_type, revealed)| StoredTile {
+                position,
+                tile_type,
+                revealed: revealed.is_some(),
+            })
+            .collect(),
+        entities,
+        dungeon: dungeon.clone(),
+    };
+    match save.write(&settings.path) {
+        Ok(()) => game_log.push(format!("Game saved to {}", settings.path.display())),
+        Err(err) => {
+            eprintln!("Failed to save to {}: {}", settings.path.display(), err);
+            game_log.push(format!("Could not save the game: {}", err));
+        }
+    }
+}
+
+/// Replaces the whole world with the saved one. A save that can't be read leaves the
+/// current run untouched.
+#[allow(clippy::too_many_arguments)]
+fn load_game(
+    mut commands: Commands,
+    mut events: EventReader<LoadGameEvent>,
+    mut replaced_events: EventWriter<MapReplacedEvent>,
+    mut game_log: ResMut<GameLog>,
+    mut rng: ResMut<GameRng>,
+    mut depth: ResMut<Depth>,
+    mut dungeon: ResMut<Dungeon>,
+    mut memory: ResMut<Memory>,
+    mut stats: ResMut<RunStats>,
+    mut states: ResMut<State<GameState>>,
+    settings: Res<SaveSettings>,
+    roots: Query<Entity, With<MapRoot>>,
+    level_entities: Query<Entity, (Or<(With<Position>, With<InBackpack>)>, Without<Parent>)>,
+) {
+    if events.iter().last().is_none() {
+        return;
+    }
+
+    let mut save = match SaveGame::load(&settings.path) {
+        Ok(save) => save,
+        Err(err) => {
+            eprintln!("Failed to load {}: {}", settings.path.display(), err);
+            game_log.push(format!("Could not load the game: {}", err));
+            return;
+        }
+    };
+
+    for entity in roots.iter().chain(level_entities.iter()) {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    *rng = save.rng.clone();
+    depth.0 = save.depth;
+    *dungeon = std::mem::take(&mut save.dungeon);
+    *memory = Memory::default();
+    *game_log = GameLog::with_entries(std::mem::take(&mut save.log));
+    *stats = std::mem::take(&mut save.stats);
+    save.restore(&mut commands);
+    replaced_events.send(MapReplacedEvent);
+    game_log.push("Game loaded");
+
+    if *states.current() != GameState::Gameplay {
+        states.set(GameState::Gameplay).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+use std::str::FromStr;
+
+use crate::{
+    map_builders::{
+        BuilderChain, ConnectivityCheck, DoorPlacement, GrassScatter, MapBuilder, MapBuilderKind,
+        MapTransform, Prefab, PrefabStamp, StairsPlacement, TerrainFeatures, TorchPlacement,
+        Unreachable,
+    },
+    spawn_table::SpawnTable,
+    MAP_SIZE, MIN_MAP_SIZE,
+};
+
+/// Looks up a launch option, first as a `--name <value>` command-line argument
+/// and then as an environment variable.
+pub fn launch_option(arg: &str, env: &str) -> Option<String> {
+    std::env::args()
+        .skip_while(|a| a != arg)
+        .nth(1)
+        .or_else(|| std::env::var(env).ok())
+}
+
+/// Like [`launch_option`], but parses the value and reports it when it's not valid.
+pub fn parse_option<T: FromStr<Err = String>>(arg: &str, env: &str) -> Option<T> {
+    launch_option(arg, env).and_then(|value| match value.parse() {
+        Ok(value) => Some(value),
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        }
+    })
+}
+
+/// Parses a map width or height, which has to be at least [`MIN_MAP_SIZE`].
+pub fn parse_map_size(arg: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(size) if size >= MIN_MAP_SIZE as usize => Ok(size),
+        _ => Err(format!(
+            "{} expects a number of at least {}, got {:?}",
+            arg, MIN_MAP_SIZE, value
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MapSettings {
+    pub width: usize,
+    pub height: usize,
+    pub builder: MapBuilderKind,
+    /// Where `prefabs` were loaded from, kept so a recording can tell which ones it used
+    pub prefabs_dir: String,
+    pub prefabs: Vec<Prefab>,
+    pub transform: Option<MapTransform>,
+    pub spawn_table: SpawnTable,
+}
+
+impl MapSettings {
+    /// Reads the map size from `--width <n>` and `--height <n>` or `ROGUELIKE_WIDTH` and
+    /// `ROGUELIKE_HEIGHT`, the builder from `--builder <name>` or `ROGUELIKE_BUILDER`,
+    /// the prefabs from `--prefabs <dir>` or `ROGUELIKE_PREFABS`,
+    /// and an optional transform from `--transform <name>` or `ROGUELIKE_TRANSFORM`.
+    pub fn from_env() -> Self {
+        let size = |arg: &str, env: &str, default: u32| {
+            launch_option(arg, env)
+                .and_then(|value| match parse_map_size(arg, &value) {
+                    Ok(size) => Some(size),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        None
+                    }
+                })
+                .unwrap_or(default as usize)
+        };
+        let width = size("--width", "ROGUELIKE_WIDTH", MAP_SIZE[0]);
+        let height = size("--height", "ROGUELIKE_HEIGHT", MAP_SIZE[1]);
+        let builder = parse_option("--builder", "ROGUELIKE_BUILDER").unwrap_or_default();
+
+        let prefabs_dir = launch_option("--prefabs", "ROGUELIKE_PREFABS")
+            .unwrap_or_else(|| "assets/prefabs".to_string());
+        let prefabs = Prefab::load_dir(&prefabs_dir)
+            .into_iter()
+            .filter_map(|prefab| match prefab {
+                Ok(prefab) => Some(prefab),
+                Err(err) => {
+                    eprintln!("Skipping prefab: {}", err);
+                    None
+                }
+            })
+            .collect();
+
+        let transform = parse_option("--transform", "ROGUELIKE_TRANSFORM");
+
+        Self {
+            width,
+            height,
+            builder,
+            prefabs_dir,
+            prefabs,
+            transform,
+            spawn_table: SpawnTable::dungeon(),
+        }
+    }
+
+    pub fn create_builder(&self, depth: u32) -> Box<dyn MapBuilder> {
+        // Straight corridors look out of place in organic levels, so those get culled instead
+        let unreachable = match self.builder {
+            MapBuilderKind::Rooms | MapBuilderKind::Bsp => Unreachable::Connect,
+            MapBuilderKind::Caves | MapBuilderKind::Drunkard | MapBuilderKind::Dla => {
+                Unreachable::Cull
+            }
+        };
+        let mut chain = BuilderChain::new(self.builder.create(self.width, self.height));
+        if !self.prefabs.is_empty() {
+            chain = chain.with(PrefabStamp::new(self.prefabs.clone()));
+        }
+        // Connectivity comes after the terrain, so hazards can't end up as the only way through
+        chain = chain
+            .with(GrassScatter)
+            .with(TerrainFeatures { depth })
+            .with(ConnectivityCheck { unreachable })
+            .with(DoorPlacement)
+            .with(TorchPlacement)
+            .with(StairsPlacement { depth });
+        if let Some(transform) = self.transform {
+            chain = chain.with(transform);
+        }
+        Box::new(chain)
+    }
+}
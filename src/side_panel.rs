@@ -4,17 +4,21 @@ use itertools::Itertools;
 
 use crate::{
     combat::Health,
-    components::{Fov, Player, Position, StatusTerminal},
+    components::{Fov, Player, Position, StatusTerminal, Visible},
     items::Item,
+    light::Lantern,
     map::Map,
+    memory::Memory,
     monster::Monster,
-    utils::{TitleBarStyle, UiUtils},
+    resources::Depth,
+    utils::{Grayscale, TitleBarStyle, UiUtils},
     STATUS_PANEL_SIZE,
 };
 
 pub fn render_player_stats(
     mut terminal: Query<&mut Terminal, With<StatusTerminal>>,
-    player: Query<&Health, With<Player>>,
+    depth: Res<Depth>,
+    player: Query<(&Health, Option<&Lantern>), With<Player>>,
 ) {
     if let Ok(mut terminal) = terminal.get_single_mut() {
         terminal.clear();
@@ -23,7 +27,11 @@ pub fn render_player_stats(
             [0, STATUS_PANEL_SIZE[1] as i32 - 5],
             [STATUS_PANEL_SIZE[0], 5],
         );
-        if let Ok(health) = player.get_single() {
+        terminal.put_string(
+            [2, STATUS_PANEL_SIZE[1] as i32 - 2],
+            &format!("Depth: {}", depth.0),
+        );
+        if let Ok((health, lantern)) = player.get_single() {
             terminal.draw_titled_bar(
                 [1, STATUS_PANEL_SIZE[1] as i32 - 4],
                 &format!("HP: {}/{}", health.current, health.max),
@@ -35,15 +43,37 @@ pub fn render_player_stats(
                     empty: CharFormat::new(Color::WHITE, Color::MAROON),
                 },
             );
+            if let Some(lantern) = lantern {
+                let (filled, empty) = if lantern.is_burning() {
+                    (Color::rgb(0.8, 0.6, 0.0), Color::rgb(0.3, 0.2, 0.0))
+                } else {
+                    (Color::DARK_GRAY, Color::rgb(0.15, 0.15, 0.15))
+                };
+                terminal.draw_titled_bar(
+                    [1, STATUS_PANEL_SIZE[1] as i32 - 3],
+                    &format!("Fuel: {}/{}", lantern.fuel, lantern.max_fuel),
+                    lantern.fuel as i32,
+                    lantern.max_fuel as i32,
+                    TitleBarStyle {
+                        width: STATUS_PANEL_SIZE[0] as usize - 2,
+                        filled: CharFormat::new(Color::WHITE, filled),
+                        empty: CharFormat::new(Color::WHITE, empty),
+                    },
+                );
+            }
         }
     }
 }
 
+/// Lists the monsters and items in sight, nearest first, then the threats the player
+/// remembers but can't see anymore.
 pub fn render_visible_entities(
     map: Res<Map>,
+    memory: Res<Memory>,
     player: Query<(&Fov, &Position), With<Player>>,
-    monsters: Query<(&Name, &Health, &Tile), With<Monster>>,
-    items: Query<(&Name, &Tile), With<Item>>,
+    monsters: Query<(&Name, &Health, &Tile), (With<Monster>, With<Visible>)>,
+    items: Query<(&Name, &Tile), (With<Item>, With<Visible>)>,
+    visible: Query<(), With<Visible>>,
     mut terminal: Query<&mut Terminal, With<StatusTerminal>>,
 ) {
     let (fov, player_pos) = match player.get_single() {
@@ -80,6 +110,14 @@ pub fn render_visible_entities(
         .filter_map(|entity| items.get(*entity).ok())
         .take(max_items);
 
+    let remembered = memory
+        .ghosts()
+        .filter(|&(entity, ghost)| {
+            ghost.threat && entity.map_or(true, |entity| visible.get(entity).is_err())
+        })
+        .map(|(_, ghost)| ghost)
+        .sorted_by_key(|ghost| player_pos.distance_squared(&ghost.position));
+
     if let Ok(mut terminal) = terminal.get_single_mut() {
         for (name, health, tile) in monsters {
             terminal.put_tile([1, y], *tile);
@@ -103,5 +141,19 @@ pub fn render_visible_entities(
             terminal.put_string([3, y], name);
             y -= 2;
         }
+
+        let mut remembered = remembered.peekable();
+        if remembered.peek().is_some() && y > 1 {
+            terminal.put_string([1, y], "Remembered");
+            y -= 1;
+        }
+        for ghost in remembered {
+            if y < 1 {
+                break;
+            }
+            terminal.put_tile([1, y], ghost.tile.grayscale());
+            terminal.put_string([3, y], &ghost.name);
+            y -= 1;
+        }
     }
 }
@@ -0,0 +1,119 @@
+use std::ops::RangeInclusive;
+
+use bracket_lib::prelude::RandomNumberGenerator;
+
+use crate::map_builders::Spawn;
+
+#[derive(Debug, Clone)]
+pub struct SpawnEntry {
+    pub name: String,
+    pub kind: Spawn,
+    pub weight: u32,
+    /// Depths the entry shows up at, every depth when `None`
+    pub depths: Option<RangeInclusive<u32>>,
+}
+
+impl SpawnEntry {
+    pub fn is_available_at(&self, depth: u32) -> bool {
+        self.depths
+            .as_ref()
+            .map_or(true, |depths| depths.contains(&depth))
+    }
+}
+
+/// Weighted list of the monsters and items a level can be populated with.
+#[derive(Debug, Clone, Default)]
+pub struct SpawnTable {
+    entries: Vec<SpawnEntry>,
+}
+
+impl SpawnTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<S: Into<String>>(self, name: S, kind: Spawn, weight: u32) -> Self {
+        self.add_entry(SpawnEntry {
+            name: name.into(),
+            kind,
+            weight,
+            depths: None,
+        })
+    }
+
+    pub fn add_for_depths<S: Into<String>>(
+        self,
+        name: S,
+        kind: Spawn,
+        weight: u32,
+        depths: RangeInclusive<u32>,
+    ) -> Self {
+        self.add_entry(SpawnEntry {
+            name: name.into(),
+            kind,
+            weight,
+            depths: Some(depths),
+        })
+    }
+
+    pub fn entries(&self) -> &[SpawnEntry] {
+        &self.entries
+    }
+
+    pub fn add_entry(mut self, entry: SpawnEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    /// What the dungeon is populated with, getting nastier and richer further down.
+    pub fn dungeon() -> Self {
+        Self::new()
+            .add_for_depths("Goblin", Spawn::Monster, 10, 1..=4)
+            .add("Orc", Spawn::Monster, 3)
+            .add_for_depths("Fire imp", Spawn::Monster, 2, 2..=u32::MAX)
+            .add_for_depths("Troll", Spawn::Monster, 3, 3..=u32::MAX)
+            .add("Healing potion", Spawn::Item, 5)
+            .add_for_depths("Greater healing potion", Spawn::Item, 2, 3..=u32::MAX)
+            .add("Lamp oil", Spawn::Item, 4)
+    }
+
+    /// The entries that can show up at `depth`.
+    pub fn for_depth(&self, depth: u32) -> Self {
+        Self {
+            entries: self
+                .entries
+                .iter()
+                .filter(|entry| entry.is_available_at(depth))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Only the entries of one kind, to fill a prefab's monster or item marker.
+    pub fn of_kind(&self, kind: Spawn) -> Self {
+        Self {
+            entries: self
+                .entries
+                .iter()
+                .filter(|entry| entry.kind == kind)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> Option<&SpawnEntry> {
+        let total = self.entries.iter().map(|entry| entry.weight).sum::<u32>();
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rng.range(0, total);
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return Some(entry);
+            }
+            roll -= entry.weight;
+        }
+        None
+    }
+}
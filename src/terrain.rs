@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+
+use crate::{
+    combat::Health,
+    components::{Player, Position, TakingATurn, Visible},
+    log::GameLog,
+    map::Map,
+    resources::RunStats,
+    rng::GameRng,
+};
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(terrain_effects);
+    }
+}
+
+/// Burns and drowns whoever starts their turn on lava or in deep water.
+fn terrain_effects(
+    mut game_log: ResMut<GameLog>,
+    mut rng: ResMut<GameRng>,
+    mut stats: ResMut<RunStats>,
+    map: Res<Map>,
+    mut actors: Query<
+        (
+            &Position,
+            &mut Health,
+            &Name,
+            Option<&Visible>,
+            Option<&Player>,
+        ),
+        Added<TakingATurn>,
+    >,
+) {
+    for (position, mut health, name, visible, player) in actors.iter_mut() {
+        let tile_type = match map.tile_type(position) {
+            Some(tile_type) => tile_type,
+            None => continue,
+        };
+
+        if let Some(damage) = tile_type.damage() {
+            let was_alive = !health.is_dead();
+            let damage = damage.roll(&mut rng);
+            health.take_damage(damage);
+            if visible.is_some() {
+                game_log.push(format!("{} is burned for {} hp", name, damage));
+            }
+            if was_alive && health.is_dead() && player.is_some() {
+                stats.cause_of_death = Some("Burned to death in lava".to_string());
+            }
+        }
+
+        let drown_chance = tile_type.drown_chance();
+        if drown_chance > 0 && rng.range(0, 100) < drown_chance {
+            let was_alive = !health.is_dead();
+            let damage = rng.roll_dice(1, 4);
+            health.take_damage(damage);
+            if visible.is_some() {
+                game_log.push(format!(
+                    "{} goes under and swallows water for {} hp",
+                    name, damage
+                ));
+            }
+            if was_alive && health.is_dead() && player.is_some() {
+                stats.cause_of_death = Some("Drowned".to_string());
+            }
+        }
+    }
+}
@@ -1,18 +1,33 @@
-use bevy::prelude::*;
+use bevy::{
+    ecs::{schedule::ShouldRun, system::SystemParam},
+    prelude::*,
+};
 
 use crate::{
     combat::{combat, track_dead},
-    components::{Actor, Energy, Speed, TakingATurn},
-    handle_want_to_move, keyboard_handling, movement,
+    components::{Actor, Energy, Player, Speed, TakingATurn},
+    handle_want_to_move, keyboard_handling, movement, perform_player_action,
+    resources::{GameState, RunStats},
 };
 
 pub struct TurnPlugin;
 
 impl Plugin for TurnPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system_to_stage(CoreStage::PreUpdate, turn_begin)
+        // Nobody gets a turn in the menus or once the player is dead
+        app.add_system_to_stage(CoreStage::PreUpdate, turn_begin.with_run_criteria(in_play))
             .add_system_to_stage(CoreStage::PostUpdate, turn_end)
-            .add_system(keyboard_handling)
+            // Not in the inventory menus, which read the keyboard themselves
+            .add_system_set(
+                SystemSet::on_update(GameState::Gameplay).with_system(keyboard_handling),
+            )
+            .add_system(
+                perform_player_action
+                    .with_run_criteria(in_play)
+                    .after(keyboard_handling)
+                    .label("perform_player_action"),
+            )
+            .add_system(count_turns)
             .add_system(handle_want_to_move)
             .add_system(combat.after(handle_want_to_move))
             .add_system(movement.after(combat))
@@ -20,6 +35,39 @@ impl Plugin for TurnPlugin {
     }
 }
 
+/// Whether the game is waiting on the player: their turn has come and every other actor
+/// is done with theirs. Actions and `F5` saves only happen then: a replay plays actions back
+/// at the same point of the turn no matter how long the player took to press a key, and
+/// nobody is halfway through a turn that a save can't hold.
+#[derive(SystemParam)]
+pub struct PlayerTurn<'w, 's> {
+    players: Query<'w, 's, (), (With<Player>, With<TakingATurn>)>,
+    others: Query<'w, 's, (), (With<Actor>, With<TakingATurn>, Without<Player>)>,
+}
+
+impl<'w, 's> PlayerTurn<'w, 's> {
+    pub fn is_waiting(&self) -> bool {
+        !self.players.is_empty() && self.others.is_empty()
+    }
+}
+
+fn in_play(state: Res<State<GameState>>) -> ShouldRun {
+    if state.current().is_in_play() {
+        ShouldRun::Yes
+    } else {
+        ShouldRun::No
+    }
+}
+
+fn count_turns(
+    mut stats: ResMut<RunStats>,
+    players: Query<(), (With<Player>, Added<TakingATurn>)>,
+) {
+    if !players.is_empty() {
+        stats.turns += 1;
+    }
+}
+
 fn turn_begin(
     mut commands: Commands,
     mut q_waiting_actors: Query<(Entity, &mut Energy, &Speed), (With<Actor>, Without<TakingATurn>)>,
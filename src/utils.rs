@@ -3,8 +3,9 @@ use bevy_ascii_terminal::{CharFormat, Terminal, Tile};
 use bevy_tiled_camera::TiledProjection;
 
 use crate::{
-    components::{Layer, Position, Revealed, Visible},
+    components::{Layer, Revealed, Visible},
     map::Map,
+    viewport::Viewport,
     LOG_PANEL_SIZE, STATUS_PANEL_SIZE, WINDOW_SIZE,
 };
 
@@ -31,6 +32,29 @@ impl Grayscale for Tile {
     }
 }
 
+/// Darkens a colour by how little light falls on it, keeping dark tiles readable.
+pub trait Shade {
+    fn shaded(&self, light: f32) -> Self;
+}
+
+impl Shade for Color {
+    fn shaded(&self, light: f32) -> Self {
+        let [r, g, b, a]: [f32; 4] = (*self).into();
+        let factor = 0.35 + 0.65 * light.clamp(0.0, 1.0);
+        Color::rgba(r * factor, g * factor, b * factor, a)
+    }
+}
+
+impl Shade for Tile {
+    fn shaded(&self, light: f32) -> Self {
+        Self {
+            glyph: self.glyph,
+            fg_color: self.fg_color.shaded(light),
+            bg_color: self.bg_color.shaded(light),
+        }
+    }
+}
+
 pub struct TitleBarStyle {
     pub width: usize,
     pub filled: CharFormat,
@@ -100,6 +124,7 @@ pub fn cursor_hint(
     mut commands: Commands,
     windows: Res<Windows>,
     map: Res<Map>,
+    viewport: Res<Viewport>,
     q_camera: Query<(&Camera, &GlobalTransform, &TiledProjection)>,
     names: Query<(Entity, &Layer), Or<(With<Visible>, With<Revealed>)>>,
 ) {
@@ -111,7 +136,10 @@ pub fn cursor_hint(
                 if let Some(mut p) = proj.world_to_tile(cam_transform, p) {
                     p.x += WINDOW_SIZE[0] as i32 / 2 - STATUS_PANEL_SIZE[0] as i32;
                     p.y += WINDOW_SIZE[1] as i32 / 2 - LOG_PANEL_SIZE[1] as i32;
-                    let position = Position::new(p.x, p.y);
+                    if p.x < 0 || p.y < 0 || p.x >= viewport.width || p.y >= viewport.height {
+                        return;
+                    }
+                    let position = viewport.screen_to_world([p.x, p.y]);
                     if !map.is_in_bounds(&position) {
                         return;
                     }
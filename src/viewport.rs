@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+
+use crate::{
+    components::{Player, Position},
+    map::Map,
+    VIEWPORT_SIZE,
+};
+
+/// The part of the map shown in the map terminal. `offset` is the world position
+/// drawn at the terminal's bottom left corner.
+pub struct Viewport {
+    pub offset: Position,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            offset: Position::default(),
+            width: VIEWPORT_SIZE[0] as i32,
+            height: VIEWPORT_SIZE[1] as i32,
+        }
+    }
+}
+
+impl Viewport {
+    /// Terminal coordinates of a world position, if it's on screen.
+    pub fn world_to_screen(&self, position: &Position) -> Option<[i32; 2]> {
+        let x = position.x - self.offset.x;
+        let y = position.y - self.offset.y;
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            Some([x, y])
+        } else {
+            None
+        }
+    }
+
+    pub fn screen_to_world(&self, [x, y]: [i32; 2]) -> Position {
+        Position::new(x + self.offset.x, y + self.offset.y)
+    }
+
+    /// Moves the viewport so `target` is in the middle, but never scrolls past the map edges.
+    /// A map smaller than the viewport is centred instead.
+    pub fn center_on(&mut self, target: &Position, map_width: i32, map_height: i32) {
+        self.offset = Position::new(
+            clamp_axis(target.x, self.width, map_width),
+            clamp_axis(target.y, self.height, map_height),
+        );
+    }
+}
+
+fn clamp_axis(target: i32, view: i32, map: i32) -> i32 {
+    if map <= view {
+        (map - view) / 2
+    } else {
+        (target - view / 2).max(0).min(map - view)
+    }
+}
+
+pub struct ViewportPlugin;
+
+impl Plugin for ViewportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Viewport>()
+            .add_system(follow_player.before("render_map"));
+    }
+}
+
+fn follow_player(
+    mut viewport: ResMut<Viewport>,
+    map: Res<Map>,
+    player: Query<&Position, (With<Player>, Changed<Position>)>,
+) {
+    if let Ok(position) = player.get_single() {
+        viewport.center_on(position, map.width() as i32, map.height() as i32);
+    }
+}